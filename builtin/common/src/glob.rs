@@ -0,0 +1,115 @@
+//! Shell globs (`*`, `**`, `?`, `[...]`, `{a,b}`) translated to regexes.
+
+/// Translates a shell glob into an anchored regex. `*` and `?` stop at `/`,
+/// `**` crosses directories and `**/` may also match no directory at all.
+pub fn glob_to_regex(glob: &str) -> String {
+    translate_glob(glob, "[^/]")
+}
+
+/// Like [`glob_to_regex`], but with `*` and `?` matching `/` too, as in
+/// find's `-path`.
+pub fn path_glob_to_regex(glob: &str) -> String {
+    translate_glob(glob, ".")
+}
+
+/// `any` being the regex for a single character matched by `?`.
+fn translate_glob(glob: &str, any: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    let mut in_braces = false;
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `**/` also matches zero directories
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => {
+                regex.push_str(any);
+                regex.push('*');
+            }
+            '?' => regex.push_str(any),
+            '[' => {
+                regex.push('[');
+                if chars.peek() == Some(&'!') {
+                    chars.next();
+                    regex.push('^');
+                }
+                for ch in chars.by_ref() {
+                    if ch == ']' {
+                        break;
+                    }
+                    if ch == '\\' || ch == '[' {
+                        regex.push('\\');
+                    }
+                    regex.push(ch);
+                }
+                regex.push(']');
+            }
+            '{' => {
+                in_braces = true;
+                regex.push_str("(?:");
+            }
+            '}' if in_braces => {
+                in_braces = false;
+                regex.push(')');
+            }
+            ',' if in_braces => regex.push('|'),
+            _ => regex.push_str(&regex::escape(&ch.to_string())),
+        }
+    }
+
+    regex.push('$');
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    fn matches(glob: &str, text: &str) -> bool {
+        Regex::new(&glob_to_regex(glob)).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn star_stops_at_slash() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(!matches("*.rs", "src/main.rs"));
+        assert!(matches("src/*.rs", "src/main.rs"));
+        assert!(matches("?.c", "a.c"));
+        assert!(!matches("?.c", "ab.c"));
+    }
+
+    #[test]
+    fn double_star_crosses_directories() {
+        assert!(matches("**/*.rs", "main.rs"));
+        assert!(matches("**/*.rs", "a/b/main.rs"));
+        assert!(matches("src/**", "src/a/b"));
+        assert!(matches("a/**/b", "a/b"));
+        assert!(matches("a/**/b", "a/x/y/b"));
+    }
+
+    #[test]
+    fn classes_and_alternatives() {
+        assert!(matches("[ab].txt", "a.txt"));
+        assert!(!matches("[!ab].txt", "a.txt"));
+        assert!(matches("[!ab].txt", "c.txt"));
+        assert!(matches("*.{rs,c}", "x.c"));
+        assert!(!matches("*.{rs,c}", "x.h"));
+        assert!(matches("a+b(1).txt", "a+b(1).txt"));
+    }
+
+    #[test]
+    fn path_globs_cross_slashes() {
+        let regex = Regex::new(&path_glob_to_regex("*/target/*")).unwrap();
+        assert!(regex.is_match("./a/target/debug/x"));
+        assert!(!regex.is_match("./a/targets"));
+    }
+}
@@ -0,0 +1,4 @@
+//! Code shared by the builtins.
+
+pub mod glob;
+pub mod lines;
@@ -0,0 +1,35 @@
+//! Line matching, used by qgrep and by qfind for its `--contains` test.
+
+use regex::Regex;
+use std::io::{self, BufRead};
+
+/// Hands `f` each line of `reader`, lossily decoded and without its line
+/// terminator, along with the number of bytes it took, until `f` returns
+/// false or the input ends.
+pub fn for_each_line(
+    mut reader: impl BufRead,
+    mut f: impl FnMut(&str, usize) -> bool,
+) -> io::Result<()> {
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            return Ok(());
+        }
+        let line = String::from_utf8_lossy(&buf);
+        if !f(line.trim_end_matches(['\n', '\r']), buf.len()) {
+            return Ok(());
+        }
+    }
+}
+
+/// Whether some line of `reader` matches `regex`, reading no further than
+/// the first one that does.
+pub fn any_line_matches(regex: &Regex, reader: impl BufRead) -> io::Result<bool> {
+    let mut found = false;
+    for_each_line(reader, |line, _| {
+        found = regex.is_match(line);
+        !found
+    })?;
+    Ok(found)
+}
@@ -0,0 +1,223 @@
+use crate::output::format_size;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use text_colorizer::Colorize;
+
+/// Action applied to every match once the walk is over.
+pub enum Action {
+    Delete,
+    /// Move into this directory, already canonicalized
+    MoveTo(PathBuf),
+}
+
+fn report_error(path: &Path, err: impl std::fmt::Display) {
+    eprintln!("{} {}: {}", "Error:".red().bold(), path.display(), err);
+}
+
+/// Checks that `path` lies strictly inside `root`, without following `path`
+/// itself should it be a symlink.
+fn check_inside(path: &Path, root: &Path) -> io::Result<()> {
+    let outside = || io::Error::other("outside of the start directory");
+    let name = path.file_name().ok_or_else(outside)?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let real = fs::canonicalize(parent)?.join(name);
+    if real == root || !real.starts_with(root) {
+        return Err(outside());
+    }
+    Ok(())
+}
+
+/// Checks that every entry below the directory `dir` is among `paths`, so
+/// moving it takes nothing along that didn't match.
+fn check_all_matched(dir: &Path, paths: &HashSet<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !paths.contains(&path) {
+            return Err(io::Error::other(format!(
+                "contains {}, which didn't match",
+                path.display()
+            )));
+        }
+        if fs::symlink_metadata(&path)?.is_dir() {
+            check_all_matched(&path, paths)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether some ancestor of `path` is among `paths`.
+fn has_matched_ancestor(path: &Path, paths: &HashSet<PathBuf>) -> bool {
+    path.ancestors()
+        .skip(1)
+        .any(|ancestor| paths.contains(ancestor))
+}
+
+fn confirm(prompt: &str) -> bool {
+    eprint!("{prompt} [y/N] ");
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).is_ok() && matches!(answer.trim(), "y" | "Y" | "yes")
+}
+
+impl Action {
+    /// Applies the action to the matched `paths`, which must all be below
+    /// `root`, once confirmed. Only matched entries are affected: deleting
+    /// handles them deepest first, so directories come after their contents
+    /// and are only removed once empty, while directories are only moved if
+    /// everything below them matched too. Returns whether every entry could
+    /// be handled.
+    pub fn run(
+        &self,
+        paths: impl Iterator<Item = String>,
+        root: &Path,
+        dry_run: bool,
+        confirmed: bool,
+    ) -> bool {
+        let root = match fs::canonicalize(root) {
+            Ok(root) => root,
+            Err(err) => {
+                report_error(root, err);
+                return false;
+            }
+        };
+
+        let mut success = true;
+        let mut paths: Vec<PathBuf> = paths.map(PathBuf::from).collect();
+        paths.sort();
+        paths.dedup();
+        // Stable, so siblings stay sorted by path
+        paths.sort_by_key(|path| Reverse(path.components().count()));
+        paths.retain(|path| {
+            let refused = check_inside(path, &root).err().or_else(|| match self {
+                Action::MoveTo(dir)
+                    if fs::canonicalize(path).is_ok_and(|path| dir.starts_with(path)) =>
+                {
+                    Some(io::Error::other("contains the destination"))
+                }
+                _ => None,
+            });
+            match refused {
+                Some(err) => {
+                    report_error(path, err);
+                    success = false;
+                    false
+                }
+                None => true,
+            }
+        });
+
+        let matched: HashSet<PathBuf> = paths.iter().cloned().collect();
+        // Directories take their contents along when moved, so these must
+        // all have matched too
+        if let Action::MoveTo(_) = self {
+            paths.retain(|path| {
+                let is_dir = fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir());
+                match is_dir.then(|| check_all_matched(path, &matched)) {
+                    Some(Err(err)) => {
+                        report_error(path, err);
+                        success = false;
+                        false
+                    }
+                    _ => true,
+                }
+            });
+            let movable: HashSet<PathBuf> = paths.iter().cloned().collect();
+            paths.retain(|path| !has_matched_ancestor(path, &movable));
+        }
+        if paths.is_empty() {
+            return success;
+        }
+
+        // Moved directories hold matched entries only
+        let kept: HashSet<PathBuf> = paths.iter().cloned().collect();
+        let size: u64 = matched
+            .iter()
+            .filter(|path| kept.contains(*path) || has_matched_ancestor(path, &kept))
+            .filter_map(|path| fs::symlink_metadata(path).ok())
+            .filter(|metadata| !metadata.is_dir())
+            .map(|metadata| metadata.len())
+            .sum();
+        let summary = format!("{} entries ({})", paths.len(), format_size(size));
+        let prompt = match self {
+            Action::Delete => format!("Delete {summary}?"),
+            Action::MoveTo(dir) => format!("Move {summary} to {}?", dir.display()),
+        };
+
+        if dry_run {
+            for path in &paths {
+                match self {
+                    Action::Delete => println!("delete {}", path.display()),
+                    Action::MoveTo(dir) => println!("move {} to {}", path.display(), dir.display()),
+                }
+            }
+            eprintln!("Dry run: {}", prompt.trim_end_matches('?'));
+            return success;
+        }
+        if !confirmed {
+            if !io::stdin().is_terminal() {
+                eprintln!(
+                    "{} can't ask for confirmation, pass --yes to proceed",
+                    "Error:".red().bold()
+                );
+                return false;
+            }
+            if !confirm(&prompt) {
+                return success;
+            }
+        }
+
+        for path in &paths {
+            let res = match self {
+                Action::Delete => delete(path),
+                Action::MoveTo(dir) => move_to(path, dir),
+            };
+            if let Err(err) = res {
+                report_error(path, err);
+                success = false;
+            }
+        }
+        success
+    }
+}
+
+fn delete(path: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    // Like find's -delete, directories still holding unmatched entries stay
+    if metadata.is_dir() {
+        fs::remove_dir(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+fn move_to(path: &Path, dir: &Path) -> io::Result<()> {
+    let target = dir.join(path.file_name().unwrap_or(path.as_os_str()));
+    if fs::symlink_metadata(&target).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", target.display()),
+        ));
+    }
+    match fs::rename(path, &target) {
+        // Across file systems, regular files are copied over instead
+        Err(err) if err.raw_os_error() == Some(libc::EXDEV) => {
+            if !fs::symlink_metadata(path)?.is_file() {
+                return Err(io::Error::new(
+                    io::ErrorKind::CrossesDevices,
+                    format!(
+                        "only regular files can be moved to another file system than {}",
+                        dir.display()
+                    ),
+                ));
+            }
+            fs::copy(path, &target)?;
+            fs::remove_file(path)
+        }
+        res => res,
+    }
+}
@@ -0,0 +1,267 @@
+use crate::output::format_size;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::hash::{DefaultHasher, Hasher};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::MetadataExt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use text_colorizer::Colorize;
+
+/// Bytes read from each candidate before hashing whole files.
+const PARTIAL_SIZE: u64 = 4096;
+
+/// Files with the same contents.
+pub struct Group {
+    pub size: u64,
+    pub paths: Vec<String>,
+}
+
+impl Group {
+    /// Space freed by keeping a single copy.
+    fn reclaimable(&self) -> u64 {
+        self.size * (self.paths.len() as u64 - 1)
+    }
+}
+
+/// Groups the regular files among `paths` by contents: first by size, then
+/// by a hash of their first bytes and by a hash of the whole file, and last
+/// by comparing the files byte for byte, on up to `jobs` threads. Returns
+/// whether every file could be read.
+pub fn find(paths: impl Iterator<Item = String>, jobs: usize) -> (Vec<Group>, bool) {
+    let mut by_size: HashMap<u64, Vec<String>> = HashMap::new();
+    let mut inodes = HashSet::new();
+    for path in paths {
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        // Hard links share their data, so only the first one found counts
+        if metadata.is_file()
+            && metadata.len() > 0
+            && inodes.insert((metadata.dev(), metadata.ino()))
+        {
+            by_size.entry(metadata.len()).or_default().push(path);
+        }
+    }
+
+    let success = AtomicBool::new(true);
+    let groups = by_size
+        .into_iter()
+        .map(|(size, paths)| Group { size, paths })
+        .filter(|group| group.paths.len() > 1)
+        .collect();
+    let groups = refine(groups, jobs, &success, Some(PARTIAL_SIZE));
+    // Files no larger than the partial hash were already hashed whole
+    let (small, large) = groups
+        .into_iter()
+        .partition(|group| group.size <= PARTIAL_SIZE);
+    let mut groups: Vec<Group> = small;
+    groups.extend(refine(large, jobs, &success, None));
+    // Equal hashes make equal contents likely, not certain
+    let mut groups = compare(groups, jobs, &success);
+
+    for group in &mut groups {
+        group.paths.sort();
+    }
+    groups.sort_by(|a, b| {
+        b.reclaimable()
+            .cmp(&a.reclaimable())
+            .then_with(|| a.paths.cmp(&b.paths))
+    });
+    (groups, success.load(Ordering::Relaxed))
+}
+
+/// Splits every group by the hash of the first `limit` bytes of its files,
+/// or of the whole files, dropping those left with a single file.
+fn refine(groups: Vec<Group>, jobs: usize, success: &AtomicBool, limit: Option<u64>) -> Vec<Group> {
+    let files: Vec<(usize, &String)> = groups
+        .iter()
+        .enumerate()
+        .flat_map(|(index, group)| group.paths.iter().map(move |path| (index, path)))
+        .collect();
+
+    let next = AtomicUsize::new(0);
+    let hashes: Vec<Vec<(usize, Option<u128>)>> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.max(1))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut hashes = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some((_, path)) = files.get(index) else {
+                            break;
+                        };
+                        let hash = match hash_file(path, limit) {
+                            Ok(hash) => Some(hash),
+                            Err(err) => {
+                                eprintln!("{} {}: {}", "Error:".red().bold(), path, err);
+                                success.store(false, Ordering::Relaxed);
+                                None
+                            }
+                        };
+                        hashes.push((index, hash));
+                    }
+                    hashes
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("The thread failed"))
+            .collect()
+    });
+
+    let mut refined: HashMap<(usize, u128), Vec<String>> = HashMap::new();
+    for (index, hash) in hashes.into_iter().flatten() {
+        if let Some(hash) = hash {
+            let (group, path) = files[index];
+            refined.entry((group, hash)).or_default().push(path.clone());
+        }
+    }
+    refined
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|((group, _), paths)| Group {
+            size: groups[group].size,
+            paths,
+        })
+        .collect()
+}
+
+/// Splits every group into files with the very same contents, dropping those
+/// left with a single file.
+fn compare(groups: Vec<Group>, jobs: usize, success: &AtomicBool) -> Vec<Group> {
+    let next = AtomicUsize::new(0);
+    let compared: Vec<Vec<Group>> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.max(1))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut compared = Vec::new();
+                    while let Some(group) = groups.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        compared.extend(split_by_contents(group, success));
+                    }
+                    compared
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("The thread failed"))
+            .collect()
+    });
+    compared
+        .into_iter()
+        .flatten()
+        .filter(|group| group.paths.len() > 1)
+        .collect()
+}
+
+fn split_by_contents(group: &Group, success: &AtomicBool) -> Vec<Group> {
+    let mut split: Vec<Group> = Vec::new();
+    for path in &group.paths {
+        let mut handled = false;
+        for candidate in &mut split {
+            match same_contents(&candidate.paths[0], path) {
+                Ok(true) => {
+                    candidate.paths.push(path.clone());
+                    handled = true;
+                    break;
+                }
+                Ok(false) => {}
+                Err(err) => {
+                    eprintln!("{} {}: {}", "Error:".red().bold(), path, err);
+                    success.store(false, Ordering::Relaxed);
+                    handled = true;
+                    break;
+                }
+            }
+        }
+        if !handled {
+            split.push(Group {
+                size: group.size,
+                paths: vec![path.clone()],
+            });
+        }
+    }
+    split
+}
+
+/// Whether the files at `a` and `b` hold the same bytes.
+fn same_contents(a: &str, b: &str) -> io::Result<bool> {
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    let (mut buf_a, mut buf_b) = (vec![0; 64 * 1024], vec![0; 64 * 1024]);
+    loop {
+        let read = read_full(&mut a, &mut buf_a)?;
+        if read != read_full(&mut b, &mut buf_b)? || buf_a[..read] != buf_b[..read] {
+            return Ok(false);
+        }
+        if read == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Fills `buf` unless the end of `file` comes first, returning how much was read.
+fn read_full(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(filled)
+}
+
+/// 128 bit hash of the first `limit` bytes of `path`, or all of it, made of
+/// two differently seeded SipHashes. It's only used to tell files apart
+/// within a run, equal hashes still need their files compared.
+fn hash_file(path: &str, limit: Option<u64>) -> io::Result<u128> {
+    let file = File::open(path)?;
+    let mut reader: Box<dyn Read> = match limit {
+        Some(limit) => Box::new(file.take(limit)),
+        None => Box::new(file),
+    };
+
+    let mut low = DefaultHasher::new();
+    let mut high = DefaultHasher::new();
+    high.write_u8(0xff);
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let read = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        low.write(&buf[..read]);
+        high.write(&buf[..read]);
+    }
+    Ok(u128::from(high.finish()) << 64 | u128::from(low.finish()))
+}
+
+/// Prints each group, largest waste first, followed by the space reclaimable
+/// by keeping one file of each.
+pub fn print(groups: &[Group]) {
+    let mut out = io::stdout().lock();
+    let mut reclaimable = 0;
+    for group in groups {
+        let header = format!("{} files of {}", group.paths.len(), format_size(group.size));
+        let mut res = writeln!(out, "{}", header.bold());
+        for path in &group.paths {
+            res = res.and_then(|()| writeln!(out, "  {path}"));
+        }
+        if res.and_then(|()| writeln!(out)).is_err() {
+            return;
+        }
+        reclaimable += group.reclaimable();
+    }
+    let _ = writeln!(
+        out,
+        "{} duplicate groups, {} reclaimable",
+        groups.len(),
+        format_size(reclaimable).green().bold()
+    );
+}
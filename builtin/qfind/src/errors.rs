@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use text_colorizer::Colorize;
+
+/// Entries that couldn't be read while walking. They're skipped with a
+/// warning, or collected for a summary once the walk is over.
+pub struct Errors {
+    summary: bool,
+    collected: Mutex<Vec<(PathBuf, String)>>,
+}
+
+impl Errors {
+    pub fn new(summary: bool) -> Errors {
+        Errors {
+            summary,
+            collected: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn report(&self, path: &Path, err: &io::Error) {
+        if !self.summary {
+            eprintln!("{} {}: {}", "Warning:".yellow().bold(), path.display(), err);
+            return;
+        }
+        self.lock().push((path.to_path_buf(), err.to_string()));
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<(PathBuf, String)>> {
+        self.collected.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Prints the errors collected so far grouped by reason, the most
+    /// common first.
+    pub fn print_summary(&self) {
+        let collected = std::mem::take(&mut *self.lock());
+        if collected.is_empty() {
+            return;
+        }
+
+        let count = collected.len();
+        let mut by_reason: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        for (path, reason) in collected {
+            by_reason.entry(reason).or_default().push(path);
+        }
+        let mut by_reason: Vec<_> = by_reason.into_iter().collect();
+        by_reason.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(&b.0)));
+
+        eprintln!(
+            "{} {} entries couldn't be read",
+            "Errors:".red().bold(),
+            count
+        );
+        for (reason, mut paths) in by_reason {
+            paths.sort();
+            eprintln!("{} ({})", reason.bold(), paths.len());
+            for path in paths {
+                eprintln!("  {}", path.display());
+            }
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::ffi::OsString;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+const PLACEHOLDERS: &[&str] = &["{}", "{/}", "{//}", "{.}", "{/.}"];
+
+#[derive(Clone)]
+pub enum Exec {
+    Each(CommandTemplate),
+    Batch(CommandTemplate),
+}
+
+/// Command line whose arguments may contain placeholders replaced for each
+/// result: `{}` path, `{/}` basename, `{//}` parent, `{.}` path without
+/// extension and `{/.}` basename without extension.
+#[derive(Clone)]
+pub struct CommandTemplate {
+    args: Vec<String>,
+}
+
+impl CommandTemplate {
+    pub fn new(mut args: Vec<String>) -> Result<CommandTemplate, String> {
+        if args.is_empty() {
+            return Err("missing command to execute".to_string());
+        }
+        if !args[1..].iter().any(|arg| has_placeholder(arg)) {
+            args.push("{}".to_string());
+        }
+        Ok(CommandTemplate { args })
+    }
+
+    fn command(&self, args: Vec<OsString>) -> Command {
+        let mut command = Command::new(&self.args[0]);
+        command.args(args);
+        command
+    }
+
+    fn expand(&self, path: &Path) -> Vec<OsString> {
+        self.args[1..].iter().map(|arg| expand(arg, path)).collect()
+    }
+}
+
+fn has_placeholder(arg: &str) -> bool {
+    PLACEHOLDERS
+        .iter()
+        .any(|placeholder| arg.contains(placeholder))
+}
+
+fn expand(arg: &str, path: &Path) -> OsString {
+    if !has_placeholder(arg) {
+        return arg.into();
+    }
+
+    let name = path.file_name().unwrap_or(path.as_os_str());
+    let stem = |path: &Path| path.with_extension("").into_os_string();
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.as_os_str(),
+        _ => ".".as_ref(),
+    };
+
+    let mut result = OsString::new();
+    let mut rest = arg;
+    while let Some(start) = rest.find('{') {
+        result.push(&rest[..start]);
+        rest = &rest[start..];
+        let replacement = if rest.starts_with("{}") {
+            Some((2, path.as_os_str().to_os_string()))
+        } else if rest.starts_with("{//}") {
+            Some((4, parent.to_os_string()))
+        } else if rest.starts_with("{/.}") {
+            Some((4, stem(Path::new(name))))
+        } else if rest.starts_with("{/}") {
+            Some((3, name.to_os_string()))
+        } else if rest.starts_with("{.}") {
+            Some((3, stem(path)))
+        } else {
+            None
+        };
+        match replacement {
+            Some((len, value)) => {
+                result.push(value);
+                rest = &rest[len..];
+            }
+            None => {
+                result.push("{");
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push(rest);
+    result
+}
+
+/// Runs the command once per path on up to `jobs` threads, returning
+/// whether every command succeeded. Each command's output is captured and
+/// printed whole once it exits, so parallel commands don't interleave, but
+/// commands may finish in any order.
+pub fn run_each(template: &CommandTemplate, paths: mpsc::Receiver<String>, jobs: usize) -> bool {
+    let paths = Arc::new(Mutex::new(paths));
+    let success = Arc::new(AtomicBool::new(true));
+
+    let workers: Vec<_> = (0..jobs.max(1))
+        .map(|_| {
+            let paths = Arc::clone(&paths);
+            let success = Arc::clone(&success);
+            let template = template.clone();
+            thread::spawn(move || loop {
+                let path = match paths.lock().unwrap().recv() {
+                    Ok(path) => path,
+                    Err(_) => break,
+                };
+                if !run_captured(template.command(template.expand(Path::new(&path)))) {
+                    success.store(false, Ordering::Relaxed);
+                }
+            })
+        })
+        .collect();
+
+    for worker in workers {
+        worker.join().expect("The thread failed");
+    }
+    success.load(Ordering::Relaxed)
+}
+
+/// Runs the command with as many paths per invocation as the system's
+/// argument size limit allows, returning whether every command succeeded.
+pub fn run_batch(template: &CommandTemplate, paths: Vec<String>) -> bool {
+    if paths.is_empty() {
+        return true;
+    }
+
+    let limit = arg_max();
+    let fixed_size: usize = template
+        .args
+        .iter()
+        .filter(|arg| !has_placeholder(arg))
+        .map(|arg| arg_size(arg.as_ref()))
+        .sum();
+
+    let mut success = true;
+    let mut batch: Vec<&String> = Vec::new();
+    let mut size = fixed_size;
+    for path in &paths {
+        let path_size: usize = template.args[1..]
+            .iter()
+            .filter(|arg| has_placeholder(arg))
+            .map(|arg| arg_size(&expand(arg, Path::new(path))))
+            .sum();
+        if !batch.is_empty() && size + path_size > limit {
+            success &= run_batch_once(template, &batch);
+            batch.clear();
+            size = fixed_size;
+        }
+        batch.push(path);
+        size += path_size;
+    }
+    success &= run_batch_once(template, &batch);
+    success
+}
+
+fn run_batch_once(template: &CommandTemplate, paths: &[&String]) -> bool {
+    let mut args = Vec::new();
+    for arg in &template.args[1..] {
+        if has_placeholder(arg) {
+            args.extend(paths.iter().map(|path| expand(arg, Path::new(path))));
+        } else {
+            args.push(arg.into());
+        }
+    }
+    run(template.command(args))
+}
+
+fn run(mut command: Command) -> bool {
+    match command.status() {
+        Ok(status) => status.success(),
+        Err(err) => {
+            eprintln!("{:?}: {}", command.get_program(), err);
+            false
+        }
+    }
+}
+
+/// Like `run`, but prints the output of the command in one go once it
+/// exits rather than as it comes.
+fn run_captured(mut command: Command) -> bool {
+    match command.output() {
+        Ok(output) => {
+            // Errors writing the output, like a closed pipe, are the caller's
+            // concern, not the command's
+            let _ = io::stdout().lock().write_all(&output.stdout);
+            let _ = io::stderr().lock().write_all(&output.stderr);
+            output.status.success()
+        }
+        Err(err) => {
+            eprintln!("{:?}: {}", command.get_program(), err);
+            false
+        }
+    }
+}
+
+/// Bytes an argument takes in the new process: the string, its NUL and the
+/// argv pointer.
+fn arg_size(arg: &std::ffi::OsStr) -> usize {
+    arg.len() + 1 + std::mem::size_of::<usize>()
+}
+
+fn arg_max() -> usize {
+    let arg_max = unsafe { libc::sysconf(libc::_SC_ARG_MAX) };
+    let arg_max = if arg_max > 0 {
+        arg_max as usize
+    } else {
+        128 * 1024
+    };
+    let env_size: usize = std::env::vars_os()
+        .map(|(key, value)| arg_size(&key) + value.len() + 1)
+        .sum();
+    // Keep some headroom, the kernel also accounts for things like the auxv
+    arg_max.saturating_sub(env_size).saturating_sub(4096)
+}
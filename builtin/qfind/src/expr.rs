@@ -0,0 +1,340 @@
+//! find-like expressions combining tests with `!`, `-a`, `-o` and
+//! parentheses, adjacent tests being implicitly and-ed:
+//!
+//! ```text
+//! ( -name '*.rs' -o -name '*.c' ) -a ! -path '*/target/*'
+//! ```
+
+use crate::matcher::{MatchKind, Matcher};
+use crate::mime::MimeFilter;
+use crate::predicate::{self, Owner, Perm, SizeFilter, TimeFilter, TypeFilter};
+use common::glob::path_glob_to_regex;
+use regex::{Regex, RegexBuilder};
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Test(Test),
+}
+
+pub enum Test {
+    /// Glob on the file name
+    Name(Matcher),
+    /// Glob or regex on the path as walked
+    Path(Regex),
+    Type(TypeFilter),
+    Size(SizeFilter),
+    Time(TimeFilter),
+    Perm(Perm),
+    Owner(Owner),
+    Contains(Regex),
+    /// Lowercase, without the leading dot
+    Extension(String),
+    Mime(MimeFilter),
+}
+
+impl Expr {
+    /// `metadata` must come from `symlink_metadata` so links aren't followed.
+    pub fn eval(&self, path: &Path, metadata: &fs::Metadata) -> bool {
+        match self {
+            Expr::And(left, right) => left.eval(path, metadata) && right.eval(path, metadata),
+            Expr::Or(left, right) => left.eval(path, metadata) || right.eval(path, metadata),
+            Expr::Not(expr) => !expr.eval(path, metadata),
+            Expr::Test(test) => test.eval(path, metadata),
+        }
+    }
+}
+
+impl Test {
+    fn eval(&self, path: &Path, metadata: &fs::Metadata) -> bool {
+        match self {
+            Test::Name(matcher) => path
+                .file_name()
+                .is_some_and(|name| matcher.is_match(&name.to_string_lossy())),
+            Test::Path(regex) => regex.is_match(&path.to_string_lossy()),
+            Test::Type(types) => types.matches(path, metadata),
+            Test::Size(size) => size.matches(metadata.len()),
+            Test::Time(time) => time.matches(metadata),
+            Test::Perm(perm) => perm.matches(metadata.mode()),
+            Test::Owner(owner) => owner.matches(metadata),
+            Test::Contains(regex) => metadata.is_file() && crate::file_contains(path, regex),
+            Test::Extension(extension) => path
+                .file_name()
+                .is_some_and(|name| predicate::has_extension(&name.to_string_lossy(), extension)),
+            Test::Mime(mime) => {
+                metadata.is_file() && crate::mime_matches(path, std::slice::from_ref(mime))
+            }
+        }
+    }
+}
+
+/// Whether `arg` starts an expression, which then takes up every remaining
+/// argument.
+pub fn starts_expression(arg: &str) -> bool {
+    matches!(arg, "(" | "!" | "-not") || is_test(arg)
+}
+
+fn is_test(arg: &str) -> bool {
+    matches!(
+        arg,
+        "-name"
+            | "-iname"
+            | "-path"
+            | "-ipath"
+            | "-regex"
+            | "-iregex"
+            | "-type"
+            | "-size"
+            | "-newer"
+            | "-changed-within"
+            | "-changed-before"
+            | "-perm"
+            | "-owner"
+            | "-contains"
+            | "-extension"
+            | "-mime"
+    )
+}
+
+struct Parser<'a> {
+    tokens: &'a [String],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    /// Error message pointing at the token at `pos`, or past the last one.
+    fn error(&self, pos: usize, message: &str) -> String {
+        let mut line = String::new();
+        let mut marker = String::new();
+        for (i, token) in self.tokens.iter().enumerate() {
+            if i > 0 {
+                line.push(' ');
+            }
+            if i == pos {
+                marker = " ".repeat(line.chars().count());
+                marker.push_str(&"^".repeat(token.chars().count().max(1)));
+            }
+            line.push_str(token);
+        }
+        if pos >= self.tokens.len() {
+            marker = " ".repeat(line.chars().count() + 1);
+            marker.push('^');
+        }
+        format!("invalid expression: {message}\n\t{line}\n\t{marker}")
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_and()?;
+        while matches!(self.peek(), Some("-o" | "-or")) {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_unary()?;
+        loop {
+            match self.peek() {
+                Some("-a" | "-and") => self.pos += 1,
+                // Adjacent tests are and-ed
+                Some(token) if token != ")" && token != "-o" && token != "-or" => {}
+                _ => return Ok(expr),
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        let start = self.pos;
+        let Some(token) = self.peek() else {
+            return Err(self.error(start, "expected a test"));
+        };
+        self.pos += 1;
+
+        match token {
+            "!" | "-not" => Ok(Expr::Not(Box::new(self.parse_unary()?))),
+            "(" => {
+                let expr = self.parse_or()?;
+                if self.peek() != Some(")") {
+                    return Err(self.error(start, "missing `)` to close this `(`"));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            _ if is_test(token) => {
+                let Some(arg) = self.peek() else {
+                    return Err(self.error(start, &format!("`{token}` requires an argument")));
+                };
+                self.pos += 1;
+                parse_test(token, arg)
+                    .map(Expr::Test)
+                    .map_err(|err| self.error(start + 1, &err))
+            }
+            ")" | "-a" | "-and" | "-o" | "-or" => {
+                Err(self.error(start, &format!("expected a test, found `{token}`")))
+            }
+            _ => Err(self.error(start, &format!("unknown test `{token}`"))),
+        }
+    }
+}
+
+fn parse_test(test: &str, arg: &str) -> Result<Test, String> {
+    let regex = |pattern: &str, ignore_case| {
+        RegexBuilder::new(pattern)
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|err| err.to_string())
+    };
+    Ok(match test {
+        "-name" | "-iname" => Test::Name(
+            Matcher::new(arg, MatchKind::Glob, test == "-iname").map_err(|err| err.to_string())?,
+        ),
+        "-path" | "-ipath" => Test::Path(regex(&path_glob_to_regex(arg), test == "-ipath")?),
+        "-regex" | "-iregex" => Test::Path(regex(arg, test == "-iregex")?),
+        "-type" => {
+            let mut types = TypeFilter::default();
+            types.add(arg)?;
+            Test::Type(types)
+        }
+        "-size" => Test::Size(SizeFilter::parse(arg)?),
+        "-newer" => {
+            let modified = fs::metadata(arg)
+                .and_then(|metadata| metadata.modified())
+                .map_err(|err| format!("{arg}: {err}"))?;
+            Test::Time(TimeFilter::After(modified))
+        }
+        "-changed-within" => Test::Time(TimeFilter::After(predicate::parse_time(arg)?)),
+        "-changed-before" => Test::Time(TimeFilter::Before(predicate::parse_time(arg)?)),
+        "-perm" => Test::Perm(Perm::parse(arg)?),
+        "-owner" => Test::Owner(Owner::parse(arg)?),
+        "-contains" => Test::Contains(regex(arg, false)?),
+        "-extension" => match arg.trim_start_matches('.') {
+            "" => return Err("missing extension".to_string()),
+            extension => Test::Extension(extension.to_lowercase()),
+        },
+        "-mime" => Test::Mime(MimeFilter::parse(arg)?),
+        _ => unreachable!("not a test: {test}"),
+    })
+}
+
+/// Parses a whole expression, reporting errors with the offending token.
+pub fn parse(tokens: &[String]) -> Result<Expr, String> {
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.parse_or()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(token) => Err(parser.error(parser.pos, &format!("unexpected `{token}`"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(expr: &str) -> Vec<String> {
+        expr.split_whitespace().map(String::from).collect()
+    }
+
+    /// Evaluates `expr` against a file named `abc`, where `T` is a true test
+    /// and `F` a false one.
+    fn eval(expr: &str) -> bool {
+        let expr = expr.replace('T', "-name a*").replace('F', "-name x*");
+        let metadata = fs::symlink_metadata(env!("CARGO_MANIFEST_DIR")).unwrap();
+        parse(&tokens(&expr))
+            .unwrap()
+            .eval(Path::new("dir/abc"), &metadata)
+    }
+
+    fn error(expr: &str) -> String {
+        match parse(&tokens(expr)) {
+            Ok(_) => panic!("`{expr}` parsed"),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn operators() {
+        assert!(eval("T"));
+        assert!(!eval("F"));
+        assert!(eval("! F") && eval("-not F") && !eval("! T"));
+        assert!(eval("T -a T") && eval("T -and T") && eval("T T"));
+        assert!(!eval("T -a F") && !eval("F T"));
+        assert!(eval("F -o T") && eval("T -or F") && !eval("F -o F"));
+        assert!(eval("( T )") && eval("! ( F )"));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        // T || (F && F), not (T || F) && F
+        assert!(eval("T -o F -a F"));
+        assert!(eval("T -o F F"));
+        assert!(eval("F F -o T"));
+        assert!(!eval("( T -o F ) -a F"));
+        // ! applies to the next test only
+        assert!(!eval("! T -o F"));
+        assert!(eval("! F -a T"));
+        assert!(!eval("! ( F -o T )"));
+        assert!(eval("! ! T"));
+    }
+
+    #[test]
+    fn tests_take_their_argument() {
+        let metadata = fs::symlink_metadata(env!("CARGO_MANIFEST_DIR")).unwrap();
+        let path = Path::new("dir/Main.RS");
+        let matches = |expr: &[&str]| {
+            let tokens: Vec<String> = expr.iter().map(|token| token.to_string()).collect();
+            parse(&tokens).unwrap().eval(path, &metadata)
+        };
+        assert!(matches(&["-iname", "*.rs"]));
+        assert!(!matches(&["-name", "*.rs"]));
+        assert!(matches(&["-path", "dir/*"]));
+        assert!(matches(&["-regex", r"^dir/\w+\.RS$"]));
+        assert!(matches(&["-iregex", "main"]));
+        assert!(matches(&["-extension", ".rs"]));
+        assert!(matches(&["-type", "d"]));
+        assert!(!matches(&["-type", "f"]));
+        // A test's argument is never taken as an operator
+        assert!(!matches(&["-name", "-o"]));
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_token() {
+        assert_eq!(
+            error("-name a -o"),
+            "invalid expression: expected a test\n\t-name a -o\n\t           ^"
+        );
+        assert_eq!(
+            error("( -name a"),
+            "invalid expression: missing `)` to close this `(`\n\t( -name a\n\t^"
+        );
+        assert_eq!(
+            error("-name a )"),
+            "invalid expression: unexpected `)`\n\t-name a )\n\t        ^"
+        );
+        assert_eq!(
+            error("-name a -bogus b"),
+            "invalid expression: unknown test `-bogus`\n\t-name a -bogus b\n\t        ^^^^^^"
+        );
+        assert_eq!(
+            error("-a -name a"),
+            "invalid expression: expected a test, found `-a`\n\t-a -name a\n\t^^"
+        );
+        assert_eq!(
+            error("! -size"),
+            "invalid expression: `-size` requires an argument\n\t! -size\n\t  ^^^^^"
+        );
+        assert_eq!(
+            error("-size 10x"),
+            "invalid expression: invalid size `10x`\n\t-size 10x\n\t      ^^^"
+        );
+    }
+}
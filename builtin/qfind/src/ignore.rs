@@ -0,0 +1,309 @@
+use common::glob::glob_to_regex;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Which ignore files to honour.
+#[derive(Clone, Copy)]
+pub struct Sources {
+    /// `.gitignore` and `.git/info/exclude`
+    pub vcs: bool,
+    /// `.ignore` and `.fdignore`
+    pub custom: bool,
+}
+
+impl Sources {
+    pub fn any(&self) -> bool {
+        self.vcs || self.custom
+    }
+
+    fn files(&self) -> Vec<&'static str> {
+        let mut files = Vec::new();
+        if self.vcs {
+            files.push(".gitignore");
+        }
+        if self.custom {
+            files.extend([".ignore", ".fdignore"]);
+        }
+        files
+    }
+}
+
+struct Rule {
+    regex: Regex,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl Rule {
+    /// Parses one gitignore line, `None` for blanks and comments.
+    fn parse(line: &str) -> Option<Rule> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(line) => (true, line),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        // Patterns with an inner slash are relative to the ignore file, the
+        // others may match at any depth below it
+        let glob = match line.strip_prefix('/') {
+            Some(line) => line.to_string(),
+            None if line.contains('/') => line.to_string(),
+            None => format!("**/{line}"),
+        };
+
+        Some(Rule {
+            regex: Regex::new(&glob_to_regex(&glob)).ok()?,
+            negated,
+            dir_only,
+        })
+    }
+}
+
+/// Ignore rules of one directory, chained to those of its ancestors.
+pub struct Ignore {
+    parent: Option<Arc<Ignore>>,
+    /// Directory the rules apply to, as walked
+    base: PathBuf,
+    /// Location of `base` relative to the directory holding the ignore
+    /// files, only non-empty for files found above the start directory
+    prefix: PathBuf,
+    rules: Vec<Rule>,
+}
+
+impl Ignore {
+    /// Rules from the directories above `root`, up to the enclosing git
+    /// repository's top level. Outside a repository nothing is loaded.
+    pub fn ancestors(root: &Path, sources: Sources) -> Option<Arc<Ignore>> {
+        let canonical = root.canonicalize().ok()?;
+        let top = canonical
+            .ancestors()
+            .find(|dir| dir.join(".git").exists())?;
+
+        let dirs: Vec<&Path> = canonical
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(top))
+            .collect();
+
+        let mut ignore = None;
+        for dir in dirs.into_iter().rev() {
+            let prefix = canonical.strip_prefix(dir).unwrap_or(Path::new(""));
+            ignore = Ignore::load(ignore.clone(), dir, root, prefix, sources).or(ignore);
+        }
+        ignore
+    }
+
+    /// Rules for the entries of `dir`, adding its own ignore files if any.
+    pub fn child(parent: Option<Arc<Ignore>>, dir: &Path, sources: Sources) -> Option<Arc<Ignore>> {
+        match Ignore::load(parent.clone(), dir, dir, Path::new(""), sources) {
+            Some(ignore) => Some(ignore),
+            None => parent,
+        }
+    }
+
+    fn load(
+        parent: Option<Arc<Ignore>>,
+        dir: &Path,
+        base: &Path,
+        prefix: &Path,
+        sources: Sources,
+    ) -> Option<Arc<Ignore>> {
+        let mut contents = Vec::new();
+        if sources.vcs {
+            contents.extend(fs::read_to_string(dir.join(".git/info/exclude")));
+        }
+        for file in sources.files() {
+            contents.extend(fs::read_to_string(dir.join(file)));
+        }
+
+        let rules: Vec<Rule> = contents
+            .iter()
+            .flat_map(|content| content.lines())
+            .filter_map(Rule::parse)
+            .collect();
+        if rules.is_empty() {
+            return None;
+        }
+
+        Some(Arc::new(Ignore {
+            parent,
+            base: base.to_path_buf(),
+            prefix: prefix.to_path_buf(),
+            rules,
+        }))
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut ignore = Some(self);
+        while let Some(current) = ignore {
+            if let Ok(relative) = path.strip_prefix(&current.base) {
+                let relative = current.prefix.join(relative);
+                let relative = relative.to_string_lossy();
+                // The last matching rule wins, and deeper files beat shallower ones
+                let rule = current
+                    .rules
+                    .iter()
+                    .rev()
+                    .find(|rule| (is_dir || !rule.dir_only) && rule.regex.is_match(&relative));
+                if let Some(rule) = rule {
+                    return !rule.negated;
+                }
+            }
+            ignore = current.parent.as_deref();
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: Sources = Sources {
+        vcs: true,
+        custom: true,
+    };
+
+    /// Fresh directory with the given files, keyed by path.
+    fn tree(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("qfind-ignore-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (file, contents) in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        dir
+    }
+
+    fn rules(dir: &str, lines: &str) -> Ignore {
+        Ignore {
+            parent: None,
+            base: PathBuf::from(dir),
+            prefix: PathBuf::new(),
+            rules: lines.lines().filter_map(Rule::parse).collect(),
+        }
+    }
+
+    #[test]
+    fn blanks_and_comments_are_skipped() {
+        let ignore = rules("root", "\n# comment\n   \n\\#file\n");
+        assert_eq!(ignore.rules.len(), 1);
+        assert!(ignore.is_ignored(Path::new("root/#file"), false));
+    }
+
+    #[test]
+    fn patterns_without_slash_match_at_any_depth() {
+        let ignore = rules("root", "*.o\n/top\nsub/inner\n");
+        assert!(ignore.is_ignored(Path::new("root/a.o"), false));
+        assert!(ignore.is_ignored(Path::new("root/x/y/a.o"), false));
+        assert!(ignore.is_ignored(Path::new("root/top"), false));
+        assert!(!ignore.is_ignored(Path::new("root/x/top"), false));
+        assert!(ignore.is_ignored(Path::new("root/sub/inner"), false));
+        assert!(!ignore.is_ignored(Path::new("root/x/sub/inner"), false));
+        assert!(!ignore.is_ignored(Path::new("elsewhere/a.o"), false));
+    }
+
+    #[test]
+    fn last_matching_rule_wins() {
+        let ignore = rules("root", "*.log\n!keep.log\n");
+        assert!(ignore.is_ignored(Path::new("root/debug.log"), false));
+        assert!(!ignore.is_ignored(Path::new("root/keep.log"), false));
+
+        let ignore = rules("root", "!keep.log\n*.log\n");
+        assert!(ignore.is_ignored(Path::new("root/keep.log"), false));
+    }
+
+    #[test]
+    fn trailing_slash_only_matches_directories() {
+        let ignore = rules("root", "build/\n");
+        assert!(ignore.is_ignored(Path::new("root/build"), true));
+        assert!(!ignore.is_ignored(Path::new("root/build"), false));
+    }
+
+    #[test]
+    fn deeper_files_override_their_ancestors() {
+        let parent = Arc::new(rules("root", "*.log\nbuild/\n"));
+        let child = Ignore {
+            parent: Some(parent),
+            ..rules("root/sub", "!*.log\n")
+        };
+        assert!(!child.is_ignored(Path::new("root/sub/a.log"), false));
+        assert!(child.is_ignored(Path::new("root/a.log"), false));
+        // Rules the child doesn't match fall through to the parent
+        assert!(child.is_ignored(Path::new("root/sub/build"), true));
+    }
+
+    #[test]
+    fn sources_select_the_files_read() {
+        let dir = tree(
+            "sources",
+            &[
+                (".gitignore", "*.git\n"),
+                (".ignore", "*.custom\n"),
+                (".fdignore", "*.fd\n"),
+                (".git/info/exclude", "*.exclude\n"),
+            ],
+        );
+        let ignored = |sources, name: &str| {
+            Ignore::child(None, &dir, sources)
+                .is_some_and(|ignore| ignore.is_ignored(&dir.join(name), false))
+        };
+
+        let vcs = Sources {
+            vcs: true,
+            custom: false,
+        };
+        assert!(ignored(vcs, "a.git") && ignored(vcs, "a.exclude"));
+        assert!(!ignored(vcs, "a.custom") && !ignored(vcs, "a.fd"));
+
+        let custom = Sources {
+            vcs: false,
+            custom: true,
+        };
+        assert!(ignored(custom, "a.custom") && ignored(custom, "a.fd"));
+        assert!(!ignored(custom, "a.git") && !ignored(custom, "a.exclude"));
+
+        assert!(ignored(ALL, "a.git") && ignored(ALL, "a.fd"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn ancestors_are_loaded_up_to_the_repository() {
+        let dir = tree(
+            "ancestors",
+            &[
+                ("repo/.git/HEAD", ""),
+                ("repo/.gitignore", "*.o\n/src/generated.rs\n"),
+                ("repo/src/.gitignore", "!keep.o\n"),
+                ("repo/src/lib/lib.rs", ""),
+            ],
+        );
+        // Outside the repository, not even the directory above is looked at
+        fs::write(dir.join(".gitignore"), "*.rs\n").unwrap();
+
+        let root = dir.join("repo/src/lib");
+        let ignore = Ignore::ancestors(&root, ALL).unwrap();
+        assert!(ignore.is_ignored(&root.join("a.o"), false));
+        assert!(!ignore.is_ignored(&root.join("keep.o"), false));
+        assert!(!ignore.is_ignored(&root.join("lib.rs"), false));
+
+        // Anchored patterns stay relative to their own file
+        let src = dir.join("repo/src");
+        let ignore = Ignore::ancestors(&src, ALL).unwrap();
+        assert!(ignore.is_ignored(&src.join("generated.rs"), false));
+        assert!(!ignore.is_ignored(&root.join("generated.rs"), false));
+
+        assert!(Ignore::ancestors(&dir, ALL).is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,740 @@
+//! Persistent index of the file system, queried instead of walking it.
+//!
+//! The file starts with [`MAGIC`], the number of roots and the roots
+//! themselves, followed by every entry in walk order:
+//!
+//! ```text
+//! shared prefix length, suffix length, suffix, kind, size, mtime
+//! ```
+//!
+//! Paths are front coded against the previous entry, which keeps the index
+//! small as siblings share their whole parent path. Numbers are LEB128
+//! varints, the mtime (in nanoseconds) zigzag encoded.
+
+use crate::ignore::Ignore;
+use crate::predicate::FileKind;
+use crate::Options;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::{CString, OsStr};
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, SystemTime};
+use text_colorizer::Colorize;
+
+const MAGIC: &[u8] = b"qfind-index\x01";
+
+/// Stored as the mtime of directories that couldn't be read, so they're
+/// always scanned again.
+const UNREAD: i64 = i64::MIN;
+
+/// Kinds in the order of their on-disk codes.
+const KINDS: [FileKind; 6] = [
+    FileKind::File,
+    FileKind::Directory,
+    FileKind::Symlink,
+    FileKind::Socket,
+    FileKind::Fifo,
+    FileKind::Device,
+];
+
+#[derive(Clone)]
+struct Entry {
+    path: Vec<u8>,
+    kind: FileKind,
+    size: u64,
+    mtime: i64,
+}
+
+impl Entry {
+    fn new(path: &Path, metadata: &fs::Metadata) -> Entry {
+        Entry {
+            path: path.as_os_str().as_bytes().to_vec(),
+            kind: FileKind::from_metadata(metadata),
+            size: metadata.len(),
+            mtime: mtime_nanos(metadata),
+        }
+    }
+
+    fn path(&self) -> &Path {
+        Path::new(OsStr::from_bytes(&self.path))
+    }
+}
+
+fn mtime_nanos(metadata: &fs::Metadata) -> i64 {
+    metadata
+        .mtime()
+        .saturating_mul(1_000_000_000)
+        .saturating_add(metadata.mtime_nsec())
+}
+
+/// Index file location, `$XDG_CACHE_HOME/qfind/index` by default.
+pub fn default_database() -> PathBuf {
+    env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        .unwrap_or_else(env::temp_dir)
+        .join("qfind")
+        .join("index")
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn encode(roots: &[PathBuf], entries: &[Entry]) -> Vec<u8> {
+    let mut buf = MAGIC.to_vec();
+    write_varint(&mut buf, roots.len() as u64);
+    for root in roots {
+        let root = root.as_os_str().as_bytes();
+        write_varint(&mut buf, root.len() as u64);
+        buf.extend_from_slice(root);
+    }
+
+    let mut previous: &[u8] = &[];
+    for entry in entries {
+        let shared = previous
+            .iter()
+            .zip(&entry.path)
+            .take_while(|(a, b)| a == b)
+            .count();
+        write_varint(&mut buf, shared as u64);
+        write_varint(&mut buf, (entry.path.len() - shared) as u64);
+        buf.extend_from_slice(&entry.path[shared..]);
+        buf.push(KINDS.iter().position(|&kind| kind == entry.kind).unwrap() as u8);
+        write_varint(&mut buf, entry.size);
+        write_varint(&mut buf, ((entry.mtime << 1) ^ (entry.mtime >> 63)) as u64);
+        previous = &entry.path;
+    }
+    buf
+}
+
+/// Path, kind, size and mtime of a decoded entry.
+type EntryView<'a> = (&'a [u8], FileKind, u64, i64);
+
+/// Decodes the entries of an index one at a time, reusing the path buffer.
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    path: Vec<u8>,
+}
+
+impl<'a> Decoder<'a> {
+    /// Checks the header, returning the roots the index was built from.
+    fn new(data: &'a [u8]) -> Result<(Vec<PathBuf>, Decoder<'a>), String> {
+        let corrupt = || "corrupt index".to_string();
+        let Some(rest) = data.strip_prefix(MAGIC) else {
+            return Err("not a qfind index".to_string());
+        };
+        let mut decoder = Decoder {
+            data: rest,
+            pos: 0,
+            path: Vec::new(),
+        };
+
+        let count = decoder.varint().ok_or_else(corrupt)?;
+        let mut roots = Vec::new();
+        for _ in 0..count {
+            let len = decoder.varint().ok_or_else(corrupt)? as usize;
+            let root = decoder.bytes(len).ok_or_else(corrupt)?;
+            roots.push(PathBuf::from(OsStr::from_bytes(root)));
+        }
+        Ok((roots, decoder))
+    }
+
+    fn varint(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.data.get(self.pos)?;
+            self.pos += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    /// The next entry, its path borrowed until the following call.
+    fn next(&mut self) -> Result<Option<EntryView<'_>>, String> {
+        if self.pos == self.data.len() {
+            return Ok(None);
+        }
+        self.next_entry()
+            .map(Some)
+            .ok_or_else(|| "corrupt index".to_string())
+    }
+
+    fn next_entry(&mut self) -> Option<EntryView<'_>> {
+        let shared = self.varint()? as usize;
+        let len = self.varint()? as usize;
+        let suffix = self.bytes(len)?;
+        if shared > self.path.len() {
+            return None;
+        }
+        self.path.truncate(shared);
+        self.path.extend_from_slice(suffix);
+
+        let kind = *KINDS.get(*self.bytes(1)?.first()? as usize)?;
+        let size = self.varint()?;
+        let mtime = self.varint()?;
+        let mtime = (mtime >> 1) as i64 ^ -((mtime & 1) as i64);
+        Some((&self.path, kind, size, mtime))
+    }
+}
+
+/// Listings of the previous index, by directory.
+#[derive(Default)]
+struct Previous {
+    mtimes: HashMap<Vec<u8>, i64>,
+    children: HashMap<Vec<u8>, Vec<Entry>>,
+}
+
+impl Previous {
+    /// Loads the index at `database`, or nothing when there isn't a usable one.
+    fn load(database: &Path) -> Previous {
+        let mut previous = Previous::default();
+        let Ok(data) = fs::read(database) else {
+            return previous;
+        };
+        let Ok((_, mut decoder)) = Decoder::new(&data) else {
+            return previous;
+        };
+
+        loop {
+            let (path, kind, size, mtime) = match decoder.next() {
+                Ok(Some(entry)) => entry,
+                Ok(None) => break,
+                // Better rescan everything than trust a partial listing
+                Err(_) => return Previous::default(),
+            };
+            let entry = Entry {
+                path: path.to_vec(),
+                kind,
+                size,
+                mtime,
+            };
+            if kind == FileKind::Directory {
+                previous.mtimes.insert(entry.path.clone(), mtime);
+            }
+            if let Some(parent) = entry.path().parent() {
+                let parent = parent.as_os_str().as_bytes().to_vec();
+                previous.children.entry(parent).or_default().push(entry);
+            }
+        }
+        previous
+    }
+
+    /// Entries of `dir` as last indexed, if it hasn't changed since.
+    fn listing(&mut self, dir: &[u8], mtime: i64) -> Option<Vec<Entry>> {
+        if mtime == UNREAD || self.mtimes.get(dir) != Some(&mtime) {
+            return None;
+        }
+        Some(self.children.remove(dir).unwrap_or_default())
+    }
+}
+
+/// Kernel pseudo file systems, whose contents are not worth indexing.
+fn is_virtual_fs(path: &Path) -> bool {
+    let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(path.as_ptr(), &mut stat) } != 0 {
+        return false;
+    }
+    [
+        libc::PROC_SUPER_MAGIC,
+        libc::SYSFS_MAGIC,
+        libc::DEVPTS_SUPER_MAGIC,
+        libc::CGROUP_SUPER_MAGIC,
+        libc::CGROUP2_SUPER_MAGIC,
+        libc::DEBUGFS_MAGIC,
+        libc::TRACEFS_MAGIC,
+        libc::SECURITYFS_MAGIC,
+        libc::BPF_FS_MAGIC,
+    ]
+    .contains(&stat.f_type)
+}
+
+struct Builder<'a> {
+    options: &'a Options,
+    previous: Previous,
+    entries: Vec<Entry>,
+    scanned: usize,
+    reused: usize,
+}
+
+impl Builder<'_> {
+    /// Fills in the directory entry at `index`, then its contents.
+    fn scan_dir(&mut self, index: usize, root: &Path, parent_dev: u64) {
+        let dir = self.entries[index].path().to_path_buf();
+        // Listings reused from the previous index may hold stale metadata
+        let metadata = match fs::symlink_metadata(&dir) {
+            Ok(metadata) if metadata.is_dir() => metadata,
+            _ => return,
+        };
+        self.entries[index] = Entry::new(&dir, &metadata);
+        if metadata.dev() != parent_dev && (self.options.root_dev.is_some() || is_virtual_fs(&dir))
+        {
+            return;
+        }
+
+        let dir_bytes = dir.as_os_str().as_bytes();
+        let children = match self.previous.listing(dir_bytes, self.entries[index].mtime) {
+            Some(children) => {
+                self.reused += 1;
+                children
+            }
+            None => {
+                self.scanned += 1;
+                match read_dir(&dir) {
+                    Ok(children) => children,
+                    Err(err) => {
+                        if err.kind() != io::ErrorKind::PermissionDenied {
+                            eprintln!("{} {}: {}", "Error:".red().bold(), dir.display(), err);
+                        }
+                        self.entries[index].mtime = UNREAD;
+                        Vec::new()
+                    }
+                }
+            }
+        };
+
+        for child in children {
+            let path = child.path();
+            if self
+                .options
+                .excludes
+                .iter()
+                .any(|glob| glob.is_match(path, root))
+            {
+                continue;
+            }
+            let is_dir = child.kind == FileKind::Directory;
+            self.entries.push(child);
+            if is_dir {
+                self.scan_dir(self.entries.len() - 1, root, metadata.dev());
+            }
+        }
+    }
+}
+
+fn read_dir(dir: &Path) -> io::Result<Vec<Entry>> {
+    let mut children = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        // Entries may vanish while reading the directory
+        if let Ok(metadata) = fs::symlink_metadata(&path) {
+            children.push(Entry::new(&path, &metadata));
+        }
+    }
+    children.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(children)
+}
+
+/// Indexes `roots` into `options.database`, rescanning only the directories
+/// modified since the last build. Files in unchanged directories keep their
+/// previously indexed size and mtime.
+pub fn build(options: &Options, roots: &[PathBuf]) -> Result<(), String> {
+    let roots = roots
+        .iter()
+        .map(|root| std::path::absolute(root).map_err(|err| format!("{}: {err}", root.display())))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut builder = Builder {
+        options,
+        previous: Previous::load(&options.database),
+        entries: Vec::new(),
+        scanned: 0,
+        reused: 0,
+    };
+    for root in &roots {
+        let metadata = fs::metadata(root).map_err(|err| format!("{}: {err}", root.display()))?;
+        builder.entries.push(Entry::new(root, &metadata));
+        if metadata.is_dir() {
+            builder.scan_dir(builder.entries.len() - 1, root, metadata.dev());
+        }
+    }
+
+    let database = &options.database;
+    let write = || -> io::Result<()> {
+        if let Some(dir) = database.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Write aside and rename, so queries never see a partial index
+        let tmp = database.with_extension("tmp");
+        fs::write(&tmp, encode(&roots, &builder.entries))?;
+        fs::rename(&tmp, database)
+    };
+    write().map_err(|err| format!("{}: {err}", database.display()))?;
+
+    eprintln!(
+        "Indexed {} entries, {} directories scanned and {} unchanged",
+        builder.entries.len(),
+        builder.scanned,
+        builder.reused
+    );
+    Ok(())
+}
+
+/// Sends the indexed paths below `options.root` matching `options` on `tx`.
+/// Names, kinds, sizes and times are tested against the index, permission
+/// and ownership tests against the files themselves. Entries a walk wouldn't
+/// reach, below excluded, pruned, ignored or hidden directories, are skipped
+/// the same way.
+pub fn query(options: &Options, tx: mpsc::Sender<String>) -> Result<(), String> {
+    let database = &options.database;
+    let data = fs::read(database).map_err(|err| {
+        format!(
+            "{}: {err}, build it with `--index build`",
+            database.display()
+        )
+    })?;
+    let (_, mut decoder) =
+        Decoder::new(&data).map_err(|err| format!("{}: {err}", database.display()))?;
+
+    // Directories a walk would enter on the way to the current entry, with
+    // the ignore rules of their contents. Entries come in walk order, each
+    // directory followed by everything below it.
+    let ignore = if options.ignore.any() {
+        let ancestors = Ignore::ancestors(&options.root, options.ignore);
+        Ignore::child(ancestors, &options.root, options.ignore)
+    } else {
+        None
+    };
+    let mut entered: Vec<(PathBuf, Option<Arc<Ignore>>)> = vec![(options.root.clone(), ignore)];
+
+    while let Some((path, kind, size, mtime)) = decoder.next()? {
+        let path = Path::new(OsStr::from_bytes(path));
+        let Ok(relative) = path.strip_prefix(&options.root) else {
+            continue;
+        };
+        let depth = relative.components().count();
+        if depth == 0 || !options.depth_allows(depth) {
+            continue;
+        }
+
+        while entered.len() > 1 && !path.starts_with(&entered[entered.len() - 1].0) {
+            entered.pop();
+        }
+        let (parent, ignore) = &entered[entered.len() - 1];
+        if path.parent() != Some(parent.as_path()) {
+            continue;
+        }
+        let is_dir = kind == FileKind::Directory;
+        let name = path.file_name().unwrap_or_default();
+        if options.is_excluded(path)
+            || options.ignore.vcs && is_dir && name == ".git"
+            || ignore
+                .as_ref()
+                .is_some_and(|ignore| ignore.is_ignored(path, is_dir))
+        {
+            continue;
+        }
+
+        // Hidden directories are listed but not entered, like pruned ones
+        if is_dir
+            && !options.is_pruned(path)
+            && (options.hidden_folders || !name.as_bytes().starts_with(b"."))
+            && options.depth_allows(depth + 1)
+        {
+            let ignore = if options.ignore.any() {
+                Ignore::child(ignore.clone(), path, options.ignore)
+            } else {
+                None
+            };
+            entered.push((path.to_path_buf(), ignore));
+        }
+        if depth < options.min_depth {
+            continue;
+        }
+
+        let modified = match mtime {
+            0.. => SystemTime::UNIX_EPOCH + Duration::from_nanos(mtime as u64),
+            _ => SystemTime::UNIX_EPOCH - Duration::from_nanos(mtime.unsigned_abs()),
+        };
+        let matches = options.name_matches(path)
+            && options.types.matches_kind(kind)
+            && options.sizes.iter().all(|filter| filter.matches(size))
+            && options.times.iter().all(|time| time.matches_time(modified))
+            // Other tests need the file itself
+            && (!options.needs_file()
+                || fs::symlink_metadata(path).is_ok_and(|metadata| options.is_match(path, &metadata)));
+        if matches && tx.send(path.display().to_string()).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, kind: FileKind, size: u64, mtime: i64) -> Entry {
+        Entry {
+            path: path.as_bytes().to_vec(),
+            kind,
+            size,
+            mtime,
+        }
+    }
+
+    type Decoded = (Vec<PathBuf>, Vec<(String, FileKind, u64, i64)>);
+
+    fn decode(data: &[u8]) -> Result<Decoded, String> {
+        let (roots, mut decoder) = Decoder::new(data)?;
+        let mut entries = Vec::new();
+        while let Some((path, kind, size, mtime)) = decoder.next()? {
+            entries.push((String::from_utf8(path.to_vec()).unwrap(), kind, size, mtime));
+        }
+        Ok((roots, entries))
+    }
+
+    /// Fresh directory holding `a/one.txt`, `a/b/two.txt` and `c/three.txt`.
+    fn tree(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("qfind-index-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for file in ["a/one.txt", "a/b/two.txt", "c/three.txt"] {
+            let path = dir.join("root").join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, file).unwrap();
+        }
+        dir
+    }
+
+    /// Options using the index in `dir`, with the extra `args` after.
+    fn options(dir: &Path, args: &[&str]) -> Options {
+        let database = dir.join("index").display().to_string();
+        let args: Vec<String> = ["qfind", "", "--database", &database]
+            .iter()
+            .chain(args)
+            .map(|arg| arg.to_string())
+            .collect();
+        Options::from_args(&args).unwrap()
+    }
+
+    /// Paths `--index query` reports below `root`, relative to it.
+    fn query_paths(dir: &Path, root: &Path, args: &[&str]) -> Vec<String> {
+        let root = root.display().to_string();
+        let mut args = args.to_vec();
+        args.extend(["--index", "query", &root]);
+        let (tx, rx) = mpsc::channel();
+        query(&options(dir, &args), tx).unwrap();
+        let mut paths: Vec<String> = rx
+            .iter()
+            .map(|path| path[root.len() + 1..].to_string())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    /// Rebuilds the index at `options.database`, returning how many
+    /// directories were scanned and reused.
+    fn rebuild(options: &Options, root: &Path) -> (usize, usize) {
+        let mut builder = Builder {
+            options,
+            previous: Previous::load(&options.database),
+            entries: Vec::new(),
+            scanned: 0,
+            reused: 0,
+        };
+        let metadata = fs::metadata(root).unwrap();
+        builder.entries.push(Entry::new(root, &metadata));
+        builder.scan_dir(0, root, metadata.dev());
+        let roots = [root.to_path_buf()];
+        fs::write(&options.database, encode(&roots, &builder.entries)).unwrap();
+        (builder.scanned, builder.reused)
+    }
+
+    fn indexed(options: &Options) -> Vec<(String, u64)> {
+        let data = fs::read(&options.database).unwrap();
+        let (_, entries) = decode(&data).unwrap();
+        entries
+            .into_iter()
+            .map(|(path, _, size, _)| (path, size))
+            .collect()
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        for value in [0, 1, 0x7f, 0x80, 0x3fff, 0x4000, u32::MAX as u64, u64::MAX] {
+            let mut buf = Vec::new();
+            write_varint(&mut buf, value);
+            let mut decoder = Decoder {
+                data: &buf,
+                pos: 0,
+                path: Vec::new(),
+            };
+            assert_eq!(decoder.varint(), Some(value));
+            assert_eq!(decoder.pos, buf.len());
+        }
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let roots = vec![PathBuf::from("/home"), PathBuf::from("/srv")];
+        let entries = vec![
+            entry(
+                "/home",
+                FileKind::Directory,
+                4096,
+                1_700_000_000_000_000_000,
+            ),
+            entry("/home/user", FileKind::Directory, 4096, UNREAD),
+            entry("/home/user/file", FileKind::File, 12, -1),
+            entry("/home/user/files", FileKind::Symlink, 4, 0),
+            entry("/home/user/fifo", FileKind::Fifo, 0, i64::MAX),
+            entry("/srv", FileKind::Socket, 0, -5_000_000_000),
+            entry("/srv/sda", FileKind::Device, 0, 1),
+        ];
+
+        let (decoded_roots, decoded) = decode(&encode(&roots, &entries)).unwrap();
+        assert_eq!(decoded_roots, roots);
+        let expected: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                let path = String::from_utf8(entry.path.clone()).unwrap();
+                (path, entry.kind, entry.size, entry.mtime)
+            })
+            .collect();
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn paths_are_front_coded() {
+        let entries = [
+            entry("/long/shared/prefix/a", FileKind::File, 0, 0),
+            entry("/long/shared/prefix/b", FileKind::File, 0, 0),
+        ];
+        let data = encode(&[], &entries);
+        let prefix = b"/long/shared/prefix/";
+        assert_eq!(
+            data.windows(prefix.len()).filter(|w| w == prefix).count(),
+            1
+        );
+    }
+
+    #[test]
+    fn corrupt_indexes_are_rejected() {
+        assert_eq!(
+            decode(b"not an index").unwrap_err(),
+            "not a qfind index".to_string()
+        );
+
+        let roots = [PathBuf::from("/")];
+        let data = encode(&roots, &[entry("/file", FileKind::File, 1 << 40, 0)]);
+        // Cut anywhere but between entries
+        let header = encode(&roots, &[]).len();
+        for len in (MAGIC.len()..data.len()).filter(|&len| len != header) {
+            assert!(decode(&data[..len]).is_err(), "truncated to {len}");
+        }
+
+        // A prefix longer than the previous path
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&[0, 5, 0]);
+        assert!(decode(&data).is_err());
+    }
+
+    #[test]
+    fn queries_skip_what_a_walk_would() {
+        let dir = tree("query");
+        let root = dir.join("root");
+        fs::create_dir_all(root.join(".hidden")).unwrap();
+        fs::write(root.join(".hidden/h.txt"), "").unwrap();
+        fs::write(root.join(".gitignore"), "c/\n").unwrap();
+        rebuild(&options(&dir, &[]), &root);
+
+        assert_eq!(
+            query_paths(&dir, &root, &[]),
+            [
+                ".gitignore",
+                ".hidden",
+                "a",
+                "a/b",
+                "a/b/two.txt",
+                "a/one.txt"
+            ]
+        );
+        assert_eq!(
+            query_paths(&dir, &root, &["-H", "--no-ignore", "--min-depth", "2"]),
+            [
+                ".hidden/h.txt",
+                "a/b",
+                "a/b/two.txt",
+                "a/one.txt",
+                "c/three.txt"
+            ]
+        );
+        // Excluding a directory excludes everything below it
+        assert_eq!(
+            query_paths(&dir, &root, &["--exclude", "b", "--type", "f"]),
+            [".gitignore", "a/one.txt"]
+        );
+        // Pruned directories are reported, their contents aren't
+        assert_eq!(
+            query_paths(&dir, &root, &["--prune", "a", "--no-ignore"]),
+            [".gitignore", ".hidden", "a", "c", "c/three.txt"]
+        );
+        assert_eq!(
+            query_paths(&dir, &root.join("a"), &["--max-depth", "1"]),
+            ["b", "one.txt"]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unchanged_directories_are_reused() {
+        let dir = tree("reuse");
+        let root = dir.join("root");
+        let options = options(&dir, &[]);
+
+        assert_eq!(rebuild(&options, &root), (4, 0));
+        let first = indexed(&options);
+        assert_eq!(first.len(), 7);
+
+        assert_eq!(rebuild(&options, &root), (0, 4));
+        assert_eq!(indexed(&options), first);
+
+        // Only the modified directory is read again, so the new file shows up
+        // but the one changed in place keeps its old size
+        fs::write(root.join("a/b/new.txt"), "new").unwrap();
+        fs::write(root.join("c/three.txt"), "longer contents").unwrap();
+        assert_eq!(rebuild(&options, &root), (1, 3));
+        let paths = indexed(&options);
+        let size = |file: &str| {
+            let path = root.join(file).display().to_string();
+            paths
+                .iter()
+                .find(|(p, _)| *p == path)
+                .map(|(_, size)| *size)
+        };
+        assert_eq!(size("a/b/new.txt"), Some(3));
+        assert_eq!(size("c/three.txt"), Some("c/three.txt".len() as u64));
+
+        // Removed directories disappear along with their contents
+        fs::remove_dir_all(root.join("a/b")).unwrap();
+        assert_eq!(rebuild(&options, &root), (1, 2));
+        assert!(indexed(&options)
+            .iter()
+            .all(|(path, _)| !path.contains("/a/b")));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,97 @@
+//! `--json` output: one object per line with the metadata of each result.
+
+use crate::predicate::{self, FileKind};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// Renders results as JSON, remembering the user and group names looked up.
+#[derive(Default)]
+pub struct Renderer {
+    users: HashMap<u32, Option<String>>,
+    groups: HashMap<u32, Option<String>>,
+}
+
+impl Renderer {
+    /// Object describing the entry found at `found`, shown as `path`, or
+    /// `None` if it's gone since.
+    pub fn render(&mut self, found: &Path, path: &Path) -> Option<String> {
+        let metadata = fs::symlink_metadata(found).ok()?;
+        let kind = FileKind::from_metadata(&metadata);
+        let user = self
+            .users
+            .entry(metadata.uid())
+            .or_insert_with_key(|&uid| predicate::get_user_name(uid));
+        let user = optional_string(user.as_deref());
+        let group = self
+            .groups
+            .entry(metadata.gid())
+            .or_insert_with_key(|&gid| predicate::get_group_name(gid));
+        let group = optional_string(group.as_deref());
+        let target = match kind {
+            FileKind::Symlink => fs::read_link(found).ok(),
+            _ => None,
+        };
+        let target = optional_string(
+            target
+                .as_ref()
+                .map(|target| target.to_string_lossy())
+                .as_deref(),
+        );
+
+        Some(format!(
+            "{{\"path\":{},\"type\":\"{}\",\"size\":{},\"mode\":\"{:04o}\",\"uid\":{},\"gid\":{},\"user\":{},\"group\":{},\"mtime\":{},\"ctime\":{},\"atime\":{},\"inode\":{},\"link_target\":{}}}",
+            string(&path.to_string_lossy()),
+            kind_name(kind),
+            metadata.len(),
+            metadata.mode() & 0o7777,
+            metadata.uid(),
+            metadata.gid(),
+            user,
+            group,
+            metadata.mtime(),
+            metadata.ctime(),
+            metadata.atime(),
+            metadata.ino(),
+            target,
+        ))
+    }
+}
+
+fn kind_name(kind: FileKind) -> &'static str {
+    match kind {
+        FileKind::File => "file",
+        FileKind::Directory => "directory",
+        FileKind::Symlink => "symlink",
+        FileKind::Socket => "socket",
+        FileKind::Fifo => "pipe",
+        FileKind::Device => "device",
+    }
+}
+
+/// `text` as a JSON string literal.
+fn string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c < ' ' => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn optional_string(text: Option<&str>) -> String {
+    text.map_or_else(|| "null".to_string(), string)
+}
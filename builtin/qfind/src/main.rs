@@ -1,138 +1,592 @@
+mod action;
+mod duplicates;
+mod errors;
+mod exec;
+mod expr;
+mod ignore;
+mod index;
+mod json;
+mod matcher;
+mod mime;
+mod output;
+mod predicate;
+mod walk;
+mod watch;
+
+use action::Action;
+use common::lines;
+use errors::Errors;
+use exec::{CommandTemplate, Exec};
+use expr::Expr;
+use ignore::Sources;
+use matcher::{MatchKind, Matcher, PathGlob};
+use mime::MimeFilter;
+use output::{Format, Output, SortKey};
+use predicate::{Access, Owner, Perm, SizeFilter, TimeFilter, TypeFilter};
+use regex::Regex;
 use std::env;
 use std::fs;
 use std::io;
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc};
 use std::thread;
 use text_colorizer::Colorize;
+use watch::Watcher;
+
+const USAGE: &str = "<pattern> [start_dir] [-H] [--min-depth N] [--max-depth N] [--glob | --regex | --fuzzy] [-i] [--full-path] [--perm MODE] [--readable] [--writable] [--executable] [--exclude GLOB]... [--prune GLOB]... [--one-file-system] [--type f|d|l|s|p|x|e]... [--size [+-]N[kMGT]]... [--changed-within TIME] [--changed-before TIME] [--newer FILE] [--owner USER:GROUP] [-j JOBS] [-x | -X CMD [ARGS]... [;]] [--no-ignore] [--no-ignore-vcs] [--format TEMPLATE | --json] [-0] [-a | --relative-to DIR] [--index build [ROOTS]... | --index query] [--database FILE] [--duplicates] [--watch] [--sort path|name|size|mtime] [--reverse] [--delete | --move-to DIR] [--dry-run] [-y] [--contains REGEX] [-e EXT]... [--mime TYPE[/SUBTYPE]]... [--broken-links] [--errors] [EXPRESSION]";
+
+/// Pseudo and volatile file systems excluded when there is no config file.
+const DEFAULT_EXCLUDES: &[&str] = &["/proc", "/sys", "/dev", "/run", "/tmp", "/var/run"];
+
+/// Default exclusions, one glob per line, read from `$XDG_CONFIG_HOME/qfind/excludes`.
+/// Without that file, [`DEFAULT_EXCLUDES`] apply, an empty file excludes nothing.
+fn default_excludes() -> Vec<String> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+    let config = config_dir.and_then(|dir| fs::read_to_string(dir.join("qfind/excludes")).ok());
+    let Some(config) = config else {
+        return DEFAULT_EXCLUDES
+            .iter()
+            .map(|&glob| glob.to_string())
+            .collect();
+    };
+
+    config
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
 
-const EXCLUDED_DIRS: &[&str] = &["/proc", "/sys", "/dev", "/run", "/tmp", "/var/run"];
+enum IndexCommand {
+    Build(Vec<PathBuf>),
+    Query,
+}
 
-#[derive(Clone)]
 struct Options {
     hidden_folders: bool,
+    min_depth: usize,
     max_depth: Option<usize>,
+    matcher: Matcher,
+    full_path: bool,
+    root: PathBuf,
+    perm: Option<Perm>,
+    access: Vec<Access>,
+    excludes: Vec<PathGlob>,
+    /// Directories reported but not entered
+    prune: Vec<PathGlob>,
+    root_dev: Option<u64>,
+    types: TypeFilter,
+    sizes: Vec<SizeFilter>,
+    times: Vec<TimeFilter>,
+    owner: Option<Owner>,
+    exec: Option<Exec>,
+    jobs: usize,
+    ignore: Sources,
+    output: Output,
+    index: Option<IndexCommand>,
+    database: PathBuf,
+    duplicates: bool,
+    watch: bool,
+    action: Option<Action>,
+    dry_run: bool,
+    yes: bool,
+    contains: Option<Regex>,
+    /// Lowercase, without the leading dot
+    extensions: Vec<String>,
+    mimes: Vec<MimeFilter>,
+    broken_links: bool,
+    /// Entries that couldn't be read
+    errors: Errors,
+    expr: Option<Expr>,
 }
 
 impl Options {
-    fn new(hidden_folders: bool, max_depth: Option<usize>) -> Options {
-        Options {
-            hidden_folders,
-            max_depth,
-        }
-    }
-}
+    fn from_args(args: &[String]) -> Result<Options, String> {
+        let mut hidden_folders = false;
+        let mut min_depth = 0;
+        let mut max_depth = None;
+        let mut match_kind = MatchKind::Substring;
+        let mut ignore_case = false;
+        let mut full_path = false;
+        let mut perm = None;
+        let mut access = Vec::new();
+        let mut excludes = default_excludes();
+        let mut prune = Vec::new();
+        let mut one_file_system = false;
+        let mut types = TypeFilter::default();
+        let mut sizes = Vec::new();
+        let mut times = Vec::new();
+        let mut owner = None;
+        let mut exec = None;
+        let mut jobs = thread::available_parallelism().map_or(1, |n| n.get());
+        let mut ignore = Sources {
+            vcs: true,
+            custom: true,
+        };
+        let mut output = Output::default();
+        let mut index = None;
+        let mut database = None;
+        let mut duplicates = false;
+        let mut watch = false;
+        let mut action = None;
+        let mut dry_run = false;
+        let mut yes = false;
+        let mut contains = None;
+        let mut extensions = Vec::new();
+        let mut mimes = Vec::new();
+        let mut broken_links = false;
+        let mut errors_summary = false;
+        let mut expr = None;
+        let mut positional = Vec::new();
 
-fn visit_dirs(
-    dir: &Path,
-    file_name: &str,
-    tx: mpsc::Sender<String>,
-    options: Options,
-    depth: usize,
-) -> io::Result<()> {
-    if dir.is_dir() {
-        for entry in fs::read_dir(dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            let tx = tx.clone();
-
-            if EXCLUDED_DIRS
-                .iter()
-                .any(|&excluded| path.starts_with(excluded))
-            {
-                continue;
-            }
-
-            if let Ok(metadata) = fs::metadata(&path) {
-                if metadata.permissions().readonly() {
-                    continue;
+        let mut iter = args.iter().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "-H" => hidden_folders = true,
+                "-d" | "--max-depth" | "-max-depth" => {
+                    let depth = iter.next().ok_or("--max-depth requires a value")?;
+                    max_depth = Some(depth.parse().map_err(|_| "Invalid max depth")?);
                 }
-            } else {
-                continue;
-            }
-
-            if path.is_dir() {
-                let dir_name = path.file_name().unwrap().to_string_lossy();
-                if options.hidden_folders
-                    || !dir_name.starts_with('.')
-                        && options.max_depth.map_or(true, |max| depth < max)
-                {
-                    let file_name = file_name.to_string();
-                    let options = options.clone();
-                    thread::spawn(move || {
-                        visit_dirs(&path, &file_name, tx, options, depth + 1).unwrap();
+                "--min-depth" => {
+                    let depth = iter.next().ok_or("--min-depth requires a value")?;
+                    min_depth = depth.parse().map_err(|_| "Invalid min depth")?;
+                }
+                "-g" | "--glob" => match_kind = MatchKind::Glob,
+                "--regex" => match_kind = MatchKind::Regex,
+                "--fuzzy" => match_kind = MatchKind::Fuzzy,
+                "--iglob" => {
+                    match_kind = MatchKind::Glob;
+                    ignore_case = true;
+                }
+                "--iregex" => {
+                    match_kind = MatchKind::Regex;
+                    ignore_case = true;
+                }
+                "-i" | "--ignore-case" => ignore_case = true,
+                "-p" | "--full-path" => full_path = true,
+                "--perm" => perm = Some(Perm::parse(iter.next().ok_or("--perm requires a mode")?)?),
+                "--readable" => access.push(Access::Readable),
+                "--writable" => access.push(Access::Writable),
+                "--executable" => access.push(Access::Executable),
+                "--exclude" => {
+                    excludes.push(iter.next().ok_or("--exclude requires a glob")?.clone())
+                }
+                "--prune" => prune.push(iter.next().ok_or("--prune requires a glob")?.clone()),
+                "--one-file-system" => one_file_system = true,
+                "-t" | "--type" => types.add(iter.next().ok_or("--type requires a type")?)?,
+                "-S" | "--size" => {
+                    let size = iter.next().ok_or("--size requires a size")?;
+                    sizes.push(SizeFilter::parse(size)?);
+                }
+                "--changed-within" => {
+                    let time = iter.next().ok_or("--changed-within requires a time")?;
+                    times.push(TimeFilter::After(predicate::parse_time(time)?));
+                }
+                "--changed-before" => {
+                    let time = iter.next().ok_or("--changed-before requires a time")?;
+                    times.push(TimeFilter::Before(predicate::parse_time(time)?));
+                }
+                "--newer" => {
+                    let file = iter.next().ok_or("--newer requires a file")?;
+                    let modified = fs::metadata(file)
+                        .and_then(|metadata| metadata.modified())
+                        .map_err(|err| format!("{file}: {err}"))?;
+                    times.push(TimeFilter::After(modified));
+                }
+                "--owner" => {
+                    owner = Some(Owner::parse(
+                        iter.next().ok_or("--owner requires an owner")?,
+                    )?)
+                }
+                "-x" | "--exec" | "-X" | "--exec-batch" => {
+                    let command: Vec<String> = iter
+                        .by_ref()
+                        .take_while(|arg| *arg != ";")
+                        .cloned()
+                        .collect();
+                    let template = CommandTemplate::new(command)?;
+                    exec = Some(match arg.as_str() {
+                        "-x" | "--exec" => Exec::Each(template),
+                        _ => Exec::Batch(template),
                     });
                 }
-            } else if path
-                .file_name()
-                .unwrap()
-                .to_string_lossy()
-                .contains(file_name)
-            {
-                tx.send(path.display().to_string()).unwrap();
+                "-j" | "--jobs" => {
+                    let value = iter.next().ok_or("--jobs requires a number")?;
+                    jobs = value
+                        .parse()
+                        .map_err(|_| format!("invalid number of jobs `{value}`"))?;
+                }
+                "--no-ignore" => {
+                    ignore.vcs = false;
+                    ignore.custom = false;
+                }
+                "--no-ignore-vcs" => ignore.vcs = false,
+                "--format" => {
+                    let template = iter.next().ok_or("--format requires a template")?;
+                    output.format = Some(Format::new(template)?);
+                }
+                "--json" => output.json = true,
+                "-0" | "--print0" => output.null = true,
+                "-a" | "--absolute-path" => output.absolute = true,
+                "--relative-to" => {
+                    let dir = iter.next().ok_or("--relative-to requires a directory")?;
+                    let dir = std::path::absolute(dir).map_err(|err| format!("{dir}: {err}"))?;
+                    output.relative_to = Some(dir);
+                }
+                "--index" => {
+                    index = match iter.next().map(String::as_str) {
+                        Some("build") => Some(IndexCommand::Build(Vec::new())),
+                        Some("query") => Some(IndexCommand::Query),
+                        _ => return Err("--index requires `build` or `query`".to_string()),
+                    }
+                }
+                "--database" => {
+                    database = Some(PathBuf::from(
+                        iter.next().ok_or("--database requires a file")?,
+                    ))
+                }
+                "--duplicates" => duplicates = true,
+                "--watch" => watch = true,
+                "--sort" => {
+                    let key = iter.next().ok_or("--sort requires a key")?;
+                    output.sort = Some(SortKey::parse(key)?);
+                }
+                _ if arg.starts_with("--sort=") => {
+                    output.sort = Some(SortKey::parse(&arg["--sort=".len()..])?)
+                }
+                "-r" | "--reverse" => output.reverse = true,
+                "--delete" => action = Some(Action::Delete),
+                "--move-to" => {
+                    let dir = iter.next().ok_or("--move-to requires a directory")?;
+                    let dir = fs::canonicalize(dir).map_err(|err| format!("{dir}: {err}"))?;
+                    if !dir.is_dir() {
+                        return Err(format!("{}: not a directory", dir.display()));
+                    }
+                    action = Some(Action::MoveTo(dir));
+                }
+                "--dry-run" => dry_run = true,
+                "-y" | "--yes" => yes = true,
+                "--contains" => {
+                    let pattern = iter.next().ok_or("--contains requires a pattern")?;
+                    contains = Some(Regex::new(pattern).map_err(|err| err.to_string())?);
+                }
+                "-e" | "--extension" => {
+                    let extension = iter.next().ok_or("--extension requires an extension")?;
+                    let extension = extension.trim_start_matches('.').to_lowercase();
+                    if extension.is_empty() {
+                        return Err("--extension requires an extension".to_string());
+                    }
+                    extensions.push(extension);
+                }
+                "--mime" => {
+                    let mime = iter.next().ok_or("--mime requires a type")?;
+                    mimes.push(MimeFilter::parse(mime)?);
+                }
+                "--broken-links" => broken_links = true,
+                "--errors" => errors_summary = true,
+                // The expression takes up everything left
+                _ if expr::starts_expression(arg) => {
+                    let tokens: Vec<String> =
+                        std::iter::once(arg).chain(iter.by_ref()).cloned().collect();
+                    expr = Some(expr::parse(&tokens)?);
+                }
+                _ => positional.push(arg.as_str()),
             }
         }
+
+        if output.json && (output.format.is_some() || output.null) {
+            return Err("--json can't be combined with --format or -0".to_string());
+        }
+
+        // Watching never ends, so results must be handled as they come
+        if watch
+            && (index.is_some()
+                || duplicates
+                || match_kind == MatchKind::Fuzzy
+                || output.sort.is_some()
+                || matches!(exec, Some(Exec::Batch(_))))
+        {
+            return Err(
+                "--watch can't be combined with --index, --duplicates, --fuzzy, --sort or -X"
+                    .to_string(),
+            );
+        }
+
+        if action.is_some()
+            && (exec.is_some()
+                || watch
+                || duplicates
+                || matches!(index, Some(IndexCommand::Build(_))))
+        {
+            return Err(
+                "--delete and --move-to can't be combined with -x, -X, --watch, --duplicates or --index build"
+                    .to_string(),
+            );
+        }
+
+        // Building takes roots instead of a pattern and a start directory,
+        // and indexes everything below them
+        if let Some(IndexCommand::Build(roots)) = &mut index {
+            roots.extend(positional.drain(..).map(PathBuf::from));
+            if roots.is_empty() {
+                roots.push(PathBuf::from("/"));
+            }
+            positional.push("");
+        }
+        // `--duplicates DIR` compares every file below DIR, and an expression
+        // replaces the pattern
+        if (duplicates && positional.len() == 1) || (expr.is_some() && positional.len() < 2) {
+            positional.insert(0, "");
+        }
+        let pattern = *positional.first().ok_or("Missing pattern")?;
+        let start_dir = match positional.get(1..).and_then(|rest| rest.last()) {
+            Some(dir) => *dir,
+            // The index holds absolute paths, all of them searched by default
+            None if matches!(index, Some(IndexCommand::Query)) => "/",
+            None => ".",
+        };
+        let root = match index {
+            Some(IndexCommand::Query) => {
+                std::path::absolute(start_dir).map_err(|err| format!("{start_dir}: {err}"))?
+            }
+            _ => PathBuf::from(start_dir),
+        };
+        // Fuzzy patterns are scored against the whole path
+        let full_path = full_path || match_kind == MatchKind::Fuzzy;
+        let matcher =
+            Matcher::new(pattern, match_kind, ignore_case).map_err(|err| err.to_string())?;
+
+        let globs = |globs: Vec<String>| {
+            globs
+                .iter()
+                .map(|glob| PathGlob::new(glob))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| err.to_string())
+        };
+        let excludes = globs(excludes)?;
+        let prune = globs(prune)?;
+
+        let root_dev = if one_file_system {
+            let metadata = fs::metadata(start_dir).map_err(|err| format!("{start_dir}: {err}"))?;
+            Some(metadata.dev())
+        } else {
+            None
+        };
+
+        Ok(Options {
+            hidden_folders,
+            min_depth,
+            max_depth,
+            matcher,
+            full_path,
+            root,
+            perm,
+            access,
+            excludes,
+            prune,
+            root_dev,
+            types,
+            sizes,
+            times,
+            owner,
+            exec,
+            jobs,
+            ignore,
+            output,
+            index,
+            database: database.unwrap_or_else(index::default_database),
+            duplicates,
+            watch,
+            action,
+            dry_run,
+            yes,
+            contains,
+            extensions,
+            mimes,
+            broken_links,
+            errors: Errors::new(errors_summary),
+            expr,
+        })
+    }
+
+    /// Whether entries `depth` levels below the start directory (its own
+    /// entries being at depth 1) may be visited.
+    fn depth_allows(&self, depth: usize) -> bool {
+        self.max_depth.is_none_or(|max| depth <= max)
+    }
+
+    fn name_matches(&self, path: &Path) -> bool {
+        let name = path.file_name().unwrap().to_string_lossy();
+        let matched = if self.full_path {
+            let relative = path.strip_prefix(&self.root).unwrap_or(path);
+            self.matcher.is_match(&relative.to_string_lossy())
+        } else {
+            self.matcher.is_match(&name)
+        };
+        matched
+            && (self.extensions.is_empty()
+                || self
+                    .extensions
+                    .iter()
+                    .any(|extension| predicate::has_extension(&name, extension)))
+    }
+
+    fn is_excluded(&self, path: &Path) -> bool {
+        self.excludes
+            .iter()
+            .any(|glob| glob.is_match(path, &self.root))
+    }
+
+    fn is_pruned(&self, path: &Path) -> bool {
+        self.prune
+            .iter()
+            .any(|glob| glob.is_match(path, &self.root))
+    }
+
+    /// Whether some test needs more than the name, kind, size and times.
+    fn needs_file(&self) -> bool {
+        self.perm.is_some()
+            || !self.access.is_empty()
+            || self.owner.is_some()
+            || self.types.needs_file()
+            || self.contains.is_some()
+            || !self.mimes.is_empty()
+            || self.broken_links
+            || self.expr.is_some()
+    }
+
+    fn is_match(&self, path: &Path, metadata: &fs::Metadata) -> bool {
+        self.name_matches(path)
+            && self.types.matches(path, metadata)
+            && self.sizes.iter().all(|size| size.matches(metadata.len()))
+            && self.times.iter().all(|time| time.matches(metadata))
+            && self.owner.is_none_or(|owner| owner.matches(metadata))
+            && self.perm.is_none_or(|perm| perm.matches(metadata.mode()))
+            && self.access.iter().all(|access| access.check(path))
+            && self
+                .contains
+                .as_ref()
+                .is_none_or(|regex| metadata.is_file() && file_contains(path, regex))
+            && (!self.broken_links || predicate::is_broken_link(path, metadata))
+            && (self.mimes.is_empty() || metadata.is_file() && mime_matches(path, &self.mimes))
+            && self
+                .expr
+                .as_ref()
+                .is_none_or(|expr| expr.eval(path, metadata))
     }
-    Ok(())
+}
+
+/// Whether some line of the file at `path` matches `regex`, unreadable
+/// files never matching.
+fn file_contains(path: &Path, regex: &Regex) -> bool {
+    fs::File::open(path)
+        .and_then(|file| lines::any_line_matches(regex, io::BufReader::new(file)))
+        .unwrap_or(false)
+}
+
+/// Whether the contents of the file at `path` look like one of the `mimes`,
+/// unreadable files never matching.
+fn mime_matches(path: &Path, mimes: &[MimeFilter]) -> bool {
+    mime::sniff(path).is_ok_and(|mime| mimes.iter().any(|filter| filter.matches(mime)))
 }
 
 fn main() -> io::Result<()> {
     let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
+    let options = Options::from_args(&args).unwrap_or_else(|err| {
         eprintln!(
-            "{} usage: {} <file_name> [start_dir] [-H] [-max-depth X]",
+            "{} {}\n\tusage: {} {}",
             "Error:".red().bold(),
-            args[0]
+            err,
+            args[0],
+            USAGE
         );
         std::process::exit(1);
-    }
+    });
 
-    let mut hidden_folders = false;
-    let mut max_depth = None;
-    let mut file_name = "";
-    let mut start_dir = ".";
-
-    for i in 1..args.len() {
-        match args[i].as_str() {
-            "-H" => hidden_folders = true,
-            "-max-depth" => {
-                if i + 1 < args.len() {
-                    max_depth = Some(args[i + 1].parse().expect("Invalid max depth"));
-                } else {
-                    eprintln!(
-                        "{} usage: {} <file_name> [start_dir] [-H] [-max-depth X]",
-                        "Error:".red().bold(),
-                        args[0]
-                    );
-                    std::process::exit(1);
-                }
-            }
-            _ => {
-                if file_name.is_empty() {
-                    file_name = &args[i];
-                } else {
-                    start_dir = &args[i];
-                }
-            }
+    if let Some(IndexCommand::Build(roots)) = &options.index {
+        if let Err(err) = index::build(&options, roots) {
+            eprintln!("{} {}", "Error:".red().bold(), err);
+            std::process::exit(1);
         }
+        return Ok(());
     }
 
-    let options = Options::new(hidden_folders, max_depth);
-    let path = Path::new(start_dir);
+    let options = Arc::new(options);
     let (tx, rx) = mpsc::channel();
+    let search: Box<dyn FnOnce() -> bool> = match options.index {
+        Some(IndexCommand::Query) => {
+            let options = Arc::clone(&options);
+            let query = thread::spawn(move || index::query(&options, tx));
+            Box::new(move || match query.join().expect("The thread failed") {
+                Ok(()) => true,
+                Err(err) => {
+                    eprintln!("{} {}", "Error:".red().bold(), err);
+                    false
+                }
+            })
+        }
+        _ if options.watch => {
+            let watcher = Arc::new(Watcher::new().unwrap_or_else(|err| {
+                eprintln!("{} {}", "Error:".red().bold(), err);
+                std::process::exit(1);
+            }));
+            let walk = walk::start(Arc::clone(&options), tx.clone(), Some(Arc::clone(&watcher)));
+            let options = Arc::clone(&options);
+            let watch = thread::spawn(move || {
+                let walked = walk.join();
+                // Watching never ends, so summarize each walk as it's done
+                options.errors.print_summary();
+                match watcher.run(&options, &tx) {
+                    Ok(()) => walked,
+                    Err(err) => {
+                        eprintln!("{} {}", "Error:".red().bold(), err);
+                        false
+                    }
+                }
+            });
+            // Output only stops early when stdout went away, don't wait then
+            Box::new(move || !watch.is_finished() || watch.join().expect("The thread failed"))
+        }
+        _ => {
+            let walk = walk::start(Arc::clone(&options), tx, None);
+            Box::new(move || walk.join())
+        }
+    };
 
-    visit_dirs(path, file_name, tx, options, 0)?;
-
-    for found_path in rx {
-        let found_path_buf = PathBuf::from(&found_path);
-        let parent_dir = found_path_buf.parent().unwrap().display().to_string();
-        let file_name = found_path_buf.file_name().unwrap().to_string_lossy();
-
-        println!(
-            "{}{}",
-            parent_dir.blue().bold(),
-            format!("/{}", file_name).green().bold()
-        );
+    let success = match &options.exec {
+        Some(Exec::Each(template)) => exec::run_each(template, rx, options.jobs),
+        Some(Exec::Batch(template)) => exec::run_batch(template, rx.into_iter().collect()),
+        None if options.action.is_some() => {
+            let action = options.action.as_ref().unwrap();
+            action.run(rx.into_iter(), &options.root, options.dry_run, options.yes)
+        }
+        None if options.duplicates => {
+            let (groups, success) = duplicates::find(rx.into_iter(), options.jobs);
+            duplicates::print(&groups);
+            success
+        }
+        // An explicit order replaces the ranking
+        None if matches!(options.matcher, Matcher::Fuzzy { .. })
+            && options.output.sort.is_none() =>
+        {
+            options
+                .output
+                .print_ranked(rx, &options.matcher, &options.root);
+            true
+        }
+        None => {
+            options.output.print(rx);
+            true
+        }
+    };
+    let searched = search();
+    options.errors.print_summary();
+    if !success || !searched {
+        std::process::exit(1);
     }
 
     Ok(())
@@ -0,0 +1,383 @@
+use common::glob::glob_to_regex;
+use regex::{Regex, RegexBuilder};
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MatchKind {
+    Substring,
+    Glob,
+    Regex,
+    Fuzzy,
+}
+
+#[derive(Clone)]
+pub enum Matcher {
+    Substring {
+        needle: String,
+        ignore_case: bool,
+    },
+    Regex(Regex),
+    Fuzzy {
+        pattern: Vec<char>,
+        ignore_case: bool,
+    },
+}
+
+impl Matcher {
+    pub fn new(pattern: &str, kind: MatchKind, ignore_case: bool) -> Result<Matcher, regex::Error> {
+        let regex = match kind {
+            MatchKind::Substring => {
+                let needle = if ignore_case {
+                    pattern.to_lowercase()
+                } else {
+                    pattern.to_string()
+                };
+                return Ok(Matcher::Substring {
+                    needle,
+                    ignore_case,
+                });
+            }
+            // Smart case: only a pattern with capitals is case sensitive
+            MatchKind::Fuzzy => {
+                let ignore_case = ignore_case || !pattern.chars().any(char::is_uppercase);
+                return Ok(Matcher::Fuzzy {
+                    pattern: pattern
+                        .chars()
+                        .map(|ch| fold_case(ch, ignore_case))
+                        .collect(),
+                    ignore_case,
+                });
+            }
+            MatchKind::Glob => glob_to_regex(pattern),
+            MatchKind::Regex => pattern.to_string(),
+        };
+
+        RegexBuilder::new(&regex)
+            .case_insensitive(ignore_case)
+            .build()
+            .map(Matcher::Regex)
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        match self {
+            Matcher::Substring {
+                needle,
+                ignore_case: true,
+            } => text.to_lowercase().contains(needle.as_str()),
+            Matcher::Substring { needle, .. } => text.contains(needle.as_str()),
+            Matcher::Regex(regex) => regex.is_match(text),
+            Matcher::Fuzzy {
+                pattern,
+                ignore_case,
+            } => {
+                let mut chars = text.chars().map(|ch| fold_case(ch, *ignore_case));
+                pattern.iter().all(|&wanted| chars.any(|ch| ch == wanted))
+            }
+        }
+    }
+
+    /// Fuzzy score of `text` and the byte offsets of the characters matched,
+    /// `None` if it doesn't match or the matcher isn't fuzzy.
+    pub fn fuzzy_score(&self, text: &str) -> Option<(i64, Vec<usize>)> {
+        match self {
+            Matcher::Fuzzy {
+                pattern,
+                ignore_case,
+            } => fuzzy_score(pattern, text, *ignore_case),
+            _ => None,
+        }
+    }
+}
+
+fn fold_case(ch: char, ignore_case: bool) -> char {
+    if ignore_case {
+        ch.to_lowercase().next().unwrap_or(ch)
+    } else {
+        ch
+    }
+}
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_SEPARATOR: i64 = 10;
+const BONUS_BOUNDARY: i64 = 8;
+const BONUS_CONSECUTIVE: i64 = 8;
+const BONUS_BASENAME: i64 = 6;
+const PENALTY_GAP: i64 = 1;
+
+/// Scores the best alignment of `pattern` as a subsequence of `text`.
+/// Matches right after a `/` or another word boundary, next to the previous
+/// match or in the file name score higher, while skipped characters cost.
+fn fuzzy_score(pattern: &[char], text: &str, ignore_case: bool) -> Option<(i64, Vec<usize>)> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let (m, n) = (pattern.len(), chars.len());
+    if m == 0 {
+        return Some((0, Vec::new()));
+    }
+
+    let basename = text.rfind('/').map_or(0, |slash| slash + 1);
+    let bonus: Vec<i64> = (0..n)
+        .map(|j| {
+            let (offset, ch) = chars[j];
+            let boundary = match j.checked_sub(1).map(|k| chars[k].1) {
+                None => BONUS_BOUNDARY,
+                Some('/') => BONUS_SEPARATOR,
+                Some('_' | '-' | '.' | ' ') => BONUS_BOUNDARY,
+                Some(prev) if prev.is_lowercase() && ch.is_uppercase() => BONUS_BOUNDARY,
+                _ => 0,
+            };
+            let in_basename = if offset >= basename {
+                BONUS_BASENAME
+            } else {
+                0
+            };
+            SCORE_MATCH + boundary + in_basename
+        })
+        .collect();
+
+    // scores[i * n + j]: best score with pattern[i] matched at chars[j], and
+    // from[i * n + j] where pattern[i - 1] was matched then
+    const NONE: i64 = i64::MIN / 2;
+    let mut scores = vec![NONE; m * n];
+    let mut from = vec![0; m * n];
+    for i in 0..m {
+        // Best of scores[i - 1][k] + k * PENALTY_GAP over k < j - 1, so the
+        // gap up to j costs (j - 1) * PENALTY_GAP less that
+        let mut gapped = (NONE, 0);
+        for j in 0..n {
+            if i > 0 && j >= 2 {
+                let k = j - 2;
+                let score = scores[(i - 1) * n + k];
+                if score > NONE && score + k as i64 * PENALTY_GAP > gapped.0 {
+                    gapped = (score + k as i64 * PENALTY_GAP, k);
+                }
+            }
+            if fold_case(chars[j].1, ignore_case) != pattern[i] {
+                continue;
+            }
+
+            let previous = if i == 0 {
+                Some((0, 0))
+            } else {
+                let mut best = None;
+                if gapped.0 > NONE {
+                    best = Some((gapped.0 - (j as i64 - 1) * PENALTY_GAP, gapped.1));
+                }
+                if j >= 1 && scores[(i - 1) * n + j - 1] > NONE {
+                    let consecutive = scores[(i - 1) * n + j - 1] + BONUS_CONSECUTIVE;
+                    if best.is_none_or(|(score, _)| consecutive >= score) {
+                        best = Some((consecutive, j - 1));
+                    }
+                }
+                best
+            };
+            if let Some((score, k)) = previous {
+                scores[i * n + j] = score + bonus[j];
+                from[i * n + j] = k;
+            }
+        }
+    }
+
+    let last = (m - 1) * n;
+    let (mut j, &score) = scores[last..]
+        .iter()
+        .enumerate()
+        .filter(|(_, &score)| score > NONE)
+        .max_by_key(|(_, &score)| score)?;
+
+    let mut positions = vec![0; m];
+    for i in (0..m).rev() {
+        positions[i] = chars[j].0;
+        j = from[i * n + j];
+    }
+    Some((score, positions))
+}
+
+/// Glob tested against a walked path. Like gitignore, a pattern without a `/`
+/// tests the file name, one starting with `/` the path as walked and any
+/// other one the path relative to the start directory.
+#[derive(Clone)]
+pub struct PathGlob {
+    regex: Regex,
+    anchor: Anchor,
+}
+
+#[derive(Clone, Copy)]
+enum Anchor {
+    Name,
+    Relative,
+    Absolute,
+}
+
+impl PathGlob {
+    pub fn new(glob: &str) -> Result<PathGlob, regex::Error> {
+        let glob = glob.trim_end_matches('/');
+        let anchor = if glob.starts_with('/') {
+            Anchor::Absolute
+        } else if glob.contains('/') {
+            Anchor::Relative
+        } else {
+            Anchor::Name
+        };
+        Ok(PathGlob {
+            regex: Regex::new(&glob_to_regex(glob))?,
+            anchor,
+        })
+    }
+
+    pub fn is_match(&self, path: &Path, root: &Path) -> bool {
+        match self.anchor {
+            Anchor::Name => path
+                .file_name()
+                .is_some_and(|name| self.regex.is_match(&name.to_string_lossy())),
+            Anchor::Relative => {
+                let relative = path.strip_prefix(root).unwrap_or(path);
+                self.regex.is_match(&relative.to_string_lossy())
+            }
+            Anchor::Absolute => self.regex.is_match(&path.to_string_lossy()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(pattern: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+        Matcher::new(pattern, MatchKind::Fuzzy, false)
+            .unwrap()
+            .fuzzy_score(text)
+    }
+
+    /// Best score over every alignment of the lowercase `pattern` in `text`,
+    /// the slow way.
+    fn brute_force(pattern: &[char], text: &str) -> Option<i64> {
+        fn best(
+            pattern: &[char],
+            chars: &[char],
+            bonus: &[i64],
+            previous: Option<usize>,
+        ) -> Option<i64> {
+            let Some((&wanted, rest)) = pattern.split_first() else {
+                return Some(0);
+            };
+            let start = previous.map_or(0, |k| k + 1);
+            (start..chars.len())
+                .filter(|&j| fold_case(chars[j], true) == wanted)
+                .filter_map(|j| {
+                    let link = match previous {
+                        None => 0,
+                        Some(k) if j == k + 1 => BONUS_CONSECUTIVE,
+                        Some(k) => -((j - k - 1) as i64) * PENALTY_GAP,
+                    };
+                    Some(bonus[j] + link + best(rest, chars, bonus, Some(j))?)
+                })
+                .max()
+        }
+
+        let chars: Vec<char> = text.chars().collect();
+        let basename = text.rfind('/').map_or(0, |slash| slash + 1);
+        let bonus: Vec<i64> = text
+            .char_indices()
+            .enumerate()
+            .map(|(j, (offset, ch))| {
+                let previous = j.checked_sub(1).map(|k| chars[k]);
+                let boundary = match previous {
+                    Some('/') => BONUS_SEPARATOR,
+                    None | Some('_' | '-' | '.' | ' ') => BONUS_BOUNDARY,
+                    Some(prev) if prev.is_lowercase() && ch.is_uppercase() => BONUS_BOUNDARY,
+                    _ => 0,
+                };
+                let in_basename = if offset >= basename {
+                    BONUS_BASENAME
+                } else {
+                    0
+                };
+                SCORE_MATCH + boundary + in_basename
+            })
+            .collect();
+        best(pattern, &chars, &bonus, None)
+    }
+
+    #[test]
+    fn empty_and_missing_patterns() {
+        assert_eq!(score("", "anything"), Some((0, Vec::new())));
+        assert_eq!(score("abc", "acb"), None);
+        assert_eq!(score("abc", ""), None);
+        assert!(Matcher::new("abc", MatchKind::Glob, false)
+            .unwrap()
+            .fuzzy_score("abc")
+            .is_none());
+    }
+
+    #[test]
+    fn positions_are_byte_offsets() {
+        assert_eq!(score("ac", "abc").unwrap().1, [0, 2]);
+        assert_eq!(score("éa", "xéxa").unwrap().1, [1, 4]);
+    }
+
+    #[test]
+    fn smart_case() {
+        assert!(score("readme", "README.md").is_some());
+        assert!(score("Readme", "README.md").is_none());
+        assert!(score("Readme", "Readme.md").is_some());
+        assert!(Matcher::new("Readme", MatchKind::Fuzzy, true)
+            .unwrap()
+            .fuzzy_score("README.md")
+            .is_some());
+    }
+
+    #[test]
+    fn consecutive_matches_beat_scattered_ones() {
+        let (consecutive, _) = score("abc", "xabcx").unwrap();
+        let (scattered, _) = score("abc", "xaxbxcx").unwrap();
+        assert!(consecutive > scattered);
+        // The alignment picked is the consecutive one, not the first letters
+        assert_eq!(score("abc", "axbxcabc").unwrap().1, [5, 6, 7]);
+    }
+
+    #[test]
+    fn boundaries_and_file_names_score_higher() {
+        let (file_name, _) = score("main", "src/main.rs").unwrap();
+        let (directory, _) = score("main", "main/src.rs").unwrap();
+        assert!(file_name > directory);
+
+        let (separator, _) = score("m", "src/m").unwrap();
+        let (word, _) = score("m", "src_m").unwrap();
+        let (camel, _) = score("m", "srcM").unwrap();
+        let (inner, _) = score("m", "srcm").unwrap();
+        assert!(separator > word);
+        assert_eq!(word, camel);
+        assert!(word > inner);
+
+        assert_eq!(score("fb", "foo/bar/fooBar").unwrap().1, [8, 11]);
+    }
+
+    #[test]
+    fn gaps_cost_per_skipped_character() {
+        let (short, _) = score("ab", "axb").unwrap();
+        let (long, _) = score("ab", "axxxb").unwrap();
+        assert_eq!(short - long, 2 * PENALTY_GAP);
+    }
+
+    #[test]
+    fn scores_are_the_best_alignment() {
+        let texts = [
+            "abcabc",
+            "a/b/c/abc",
+            "src/a_b-c.abc",
+            "aAbBcC/abc",
+            "xaxbxcxabcaxbc",
+            "cba/abc/bca",
+        ];
+        for text in texts {
+            for pattern in ["a", "ab", "abc", "ac", "cab", "bcb", "aab"] {
+                let chars: Vec<char> = pattern.chars().collect();
+                assert_eq!(
+                    score(pattern, text).map(|(score, _)| score),
+                    brute_force(&chars, text),
+                    "{pattern} in {text}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,358 @@
+//! MIME types guessed from the first bytes of files, for `--mime`.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Bytes read from the start of each file, enough to find the tar magic.
+const HEADER_SIZE: u64 = 512;
+
+/// Type every ELF file also matches, whatever its kind.
+const ELF: &str = "application/x-elf";
+
+/// Signatures found at the start of files, from the most specific.
+const MAGICS: [(&[u8], &str); 30] = [
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"BM", "image/bmp"),
+    (b"II*\0", "image/tiff"),
+    (b"MM\0*", "image/tiff"),
+    (b"\0\0\x01\0", "image/vnd.microsoft.icon"),
+    (b"%PDF-", "application/pdf"),
+    (b"%!PS", "application/postscript"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"PK\x05\x06", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"BZh", "application/x-bzip2"),
+    (b"\xfd7zXZ\0", "application/x-xz"),
+    (b"\x28\xb5\x2f\xfd", "application/zstd"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"Rar!\x1a\x07", "application/vnd.rar"),
+    (b"\0asm", "application/wasm"),
+    (b"SQLite format 3\0", "application/vnd.sqlite3"),
+    (b"\xca\xfe\xba\xbe", "application/java-vm"),
+    (b"ID3", "audio/mpeg"),
+    (b"\xff\xfb", "audio/mpeg"),
+    (b"OggS", "audio/ogg"),
+    (b"fLaC", "audio/flac"),
+    (b"\x1a\x45\xdf\xa3", "video/x-matroska"),
+    (b"<?xml", "text/xml"),
+    (b"{\\rtf", "text/rtf"),
+    (b"#!", "text/x-script"),
+    (b"\xef\xbb\xbf", "text/plain"),
+];
+
+/// Guesses the MIME type of the regular file at `path` from its contents.
+pub fn sniff(path: &Path) -> io::Result<&'static str> {
+    let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+    File::open(path)?
+        .take(HEADER_SIZE)
+        .read_to_end(&mut header)?;
+    Ok(sniff_bytes(&header))
+}
+
+fn sniff_bytes(header: &[u8]) -> &'static str {
+    if header.is_empty() {
+        return "inode/x-empty";
+    }
+    if header.starts_with(b"\x7fELF") {
+        return match elf_type(header) {
+            Some(1) => "application/x-object",
+            Some(2) => "application/x-executable",
+            // Position independent executables are shared objects too, but
+            // ask for an interpreter
+            Some(3) if has_interpreter(header) => "application/x-pie-executable",
+            Some(3) => "application/x-sharedlib",
+            Some(4) => "application/x-coredump",
+            _ => ELF,
+        };
+    }
+    if header.starts_with(b"RIFF") {
+        match header.get(8..12) {
+            Some(b"WEBP") => return "image/webp",
+            Some(b"WAVE") => return "audio/wav",
+            Some(b"AVI ") => return "video/x-msvideo",
+            _ => {}
+        }
+    }
+    if header.get(4..8) == Some(b"ftyp") {
+        return match header.get(8..12) {
+            Some(b"heic" | b"heix" | b"mif1") => "image/heic",
+            Some(b"avif") => "image/avif",
+            Some(b"M4A ") => "audio/mp4",
+            Some(b"qt  ") => "video/quicktime",
+            _ => "video/mp4",
+        };
+    }
+    if header.get(257..262) == Some(b"ustar") {
+        return "application/x-tar";
+    }
+    if let Some((_, mime)) = MAGICS.iter().find(|(magic, _)| header.starts_with(magic)) {
+        return mime;
+    }
+
+    let start = String::from_utf8_lossy(&header[..header.len().min(64)]).to_ascii_lowercase();
+    let start = start.trim_start();
+    if start.starts_with("<!doctype html") || start.starts_with("<html") {
+        return "text/html";
+    }
+    if is_text(header) {
+        "text/plain"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+/// Reads the integer of `N` bytes at `offset` of an ELF header, in the byte
+/// order it gives.
+fn elf_int<const N: usize>(header: &[u8], offset: usize) -> Option<u64> {
+    let bytes = header.get(offset..offset + N)?;
+    let shift = |value: u64, byte: &u8| value << 8 | u64::from(*byte);
+    match header.get(5)? {
+        1 => Some(bytes.iter().rev().fold(0, shift)),
+        2 => Some(bytes.iter().fold(0, shift)),
+        _ => None,
+    }
+}
+
+fn elf_type(header: &[u8]) -> Option<u64> {
+    elf_int::<2>(header, 16)
+}
+
+/// Whether an ELF file has a `PT_INTERP` program header, among those within
+/// `header`.
+fn has_interpreter(header: &[u8]) -> bool {
+    const PT_INTERP: u64 = 3;
+    let (offset, size, count) = match header.get(4) {
+        Some(1) => (
+            elf_int::<4>(header, 28),
+            elf_int::<2>(header, 42),
+            elf_int::<2>(header, 44),
+        ),
+        Some(2) => (
+            elf_int::<8>(header, 32),
+            elf_int::<2>(header, 54),
+            elf_int::<2>(header, 56),
+        ),
+        _ => return false,
+    };
+    let (Some(offset), Some(size), Some(count)) = (offset, size, count) else {
+        return false;
+    };
+    (0..count)
+        .map_while(|i| usize::try_from(offset.checked_add(i * size)?).ok())
+        .map_while(|entry| elf_int::<4>(header, entry))
+        .any(|kind| kind == PT_INTERP)
+}
+
+fn is_elf(mime: &str) -> bool {
+    matches!(
+        mime,
+        "application/x-object"
+            | "application/x-executable"
+            | "application/x-pie-executable"
+            | "application/x-sharedlib"
+            | "application/x-coredump"
+    )
+}
+
+/// Whether `header` looks like UTF-8 text, possibly cut in the middle of a
+/// character.
+fn is_text(header: &[u8]) -> bool {
+    if header.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(header) {
+        Ok(_) => true,
+        Err(err) => err.error_len().is_none() && header.len() as u64 == HEADER_SIZE,
+    }
+}
+
+/// `--mime` pattern like `image/png`, `image/*`, `application/x-*` or just
+/// `image`. `application/x-elf` matches objects, executables, libraries and
+/// core dumps alike.
+pub struct MimeFilter {
+    kind: String,
+    subtype: Option<String>,
+}
+
+impl MimeFilter {
+    pub fn parse(spec: &str) -> Result<MimeFilter, String> {
+        let spec = spec.to_ascii_lowercase();
+        let (kind, subtype) = spec.split_once('/').unwrap_or((&spec, "*"));
+        if kind.is_empty() || subtype.is_empty() || subtype.contains('/') {
+            return Err(format!("invalid MIME type `{spec}`"));
+        }
+        Ok(MimeFilter {
+            kind: kind.to_string(),
+            subtype: (subtype != "*").then(|| subtype.to_string()),
+        })
+    }
+
+    pub fn matches(&self, mime: &str) -> bool {
+        self.matches_type(mime) || is_elf(mime) && self.matches_type(ELF)
+    }
+
+    fn matches_type(&self, mime: &str) -> bool {
+        let (kind, subtype) = mime.split_once('/').unwrap_or((mime, ""));
+        (self.kind == "*" || self.kind == kind)
+            && self
+                .subtype
+                .as_ref()
+                .is_none_or(|expected| match expected.strip_suffix('*') {
+                    Some(prefix) => subtype.starts_with(prefix),
+                    None => expected == subtype,
+                })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 64-bit little endian ELF header of type `kind`, its program headers
+    /// right after it with the given types.
+    fn elf64(kind: u16, segments: &[u32]) -> Vec<u8> {
+        let mut header = vec![0; 64];
+        header[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        header[16..18].copy_from_slice(&kind.to_le_bytes());
+        header[32..40].copy_from_slice(&64u64.to_le_bytes());
+        header[54..56].copy_from_slice(&56u16.to_le_bytes());
+        header[56..58].copy_from_slice(&(segments.len() as u16).to_le_bytes());
+        for segment in segments {
+            let mut entry = vec![0; 56];
+            entry[..4].copy_from_slice(&segment.to_le_bytes());
+            header.extend(entry);
+        }
+        header
+    }
+
+    /// 32-bit big endian ELF header, as `elf64`.
+    fn elf32(kind: u16, segments: &[u32]) -> Vec<u8> {
+        let mut header = vec![0; 52];
+        header[..6].copy_from_slice(b"\x7fELF\x01\x02");
+        header[16..18].copy_from_slice(&kind.to_be_bytes());
+        header[28..32].copy_from_slice(&52u32.to_be_bytes());
+        header[42..44].copy_from_slice(&32u16.to_be_bytes());
+        header[44..46].copy_from_slice(&(segments.len() as u16).to_be_bytes());
+        for segment in segments {
+            let mut entry = vec![0; 32];
+            entry[..4].copy_from_slice(&segment.to_be_bytes());
+            header.extend(entry);
+        }
+        header
+    }
+
+    #[test]
+    fn magic_numbers() {
+        assert_eq!(sniff_bytes(b""), "inode/x-empty");
+        assert_eq!(sniff_bytes(b"\x89PNG\r\n\x1a\n...."), "image/png");
+        assert_eq!(sniff_bytes(b"%PDF-1.7\n"), "application/pdf");
+        assert_eq!(sniff_bytes(b"PK\x03\x04rest"), "application/zip");
+        assert_eq!(sniff_bytes(b"\x1f\x8b\x08\0"), "application/gzip");
+        assert_eq!(sniff_bytes(b"#!/bin/sh\n"), "text/x-script");
+        assert_eq!(sniff_bytes(b"RIFF\0\0\0\0WEBPVP8 "), "image/webp");
+        assert_eq!(sniff_bytes(b"RIFF\0\0\0\0WAVEfmt "), "audio/wav");
+        assert_eq!(sniff_bytes(b"\0\0\0\x18ftypheic"), "image/heic");
+        assert_eq!(sniff_bytes(b"\0\0\0\x18ftypisom"), "video/mp4");
+
+        let mut tar = vec![0; 512];
+        tar[257..263].copy_from_slice(b"ustar\0");
+        assert_eq!(sniff_bytes(&tar), "application/x-tar");
+    }
+
+    #[test]
+    fn text_and_binary() {
+        assert_eq!(sniff_bytes(b"  <!DOCTYPE html>"), "text/html");
+        assert_eq!(sniff_bytes(b"<HTML><body>"), "text/html");
+        assert_eq!(sniff_bytes(b"plain \xc3\xa9 text\n"), "text/plain");
+        assert_eq!(sniff_bytes(b"nul\0byte"), "application/octet-stream");
+        assert_eq!(sniff_bytes(b"bad \xff utf-8"), "application/octet-stream");
+
+        // A character cut at the end of the header is still text, but not
+        // at the end of a shorter file
+        let mut header = vec![b'a'; HEADER_SIZE as usize - 1];
+        header.push(0xc3);
+        assert_eq!(sniff_bytes(&header), "text/plain");
+        assert_eq!(sniff_bytes(b"short \xc3"), "application/octet-stream");
+    }
+
+    #[test]
+    fn elf_kinds() {
+        const PT_LOAD: u32 = 1;
+        const PT_INTERP: u32 = 3;
+        assert_eq!(sniff_bytes(&elf64(1, &[])), "application/x-object");
+        assert_eq!(
+            sniff_bytes(&elf64(2, &[PT_LOAD])),
+            "application/x-executable"
+        );
+        assert_eq!(
+            sniff_bytes(&elf64(3, &[PT_LOAD, PT_INTERP])),
+            "application/x-pie-executable"
+        );
+        assert_eq!(
+            sniff_bytes(&elf64(3, &[PT_LOAD])),
+            "application/x-sharedlib"
+        );
+        assert_eq!(sniff_bytes(&elf64(4, &[])), "application/x-coredump");
+        assert_eq!(sniff_bytes(&elf64(0xfe00, &[])), "application/x-elf");
+
+        assert_eq!(sniff_bytes(&elf32(2, &[])), "application/x-executable");
+        assert_eq!(
+            sniff_bytes(&elf32(3, &[PT_INTERP])),
+            "application/x-pie-executable"
+        );
+        assert_eq!(
+            sniff_bytes(&elf32(3, &[PT_LOAD])),
+            "application/x-sharedlib"
+        );
+
+        // Program headers past the bytes read can't be checked
+        let mut far = elf64(3, &[PT_INTERP]);
+        far[32..40].copy_from_slice(&(HEADER_SIZE * 2).to_le_bytes());
+        assert_eq!(sniff_bytes(&far), "application/x-sharedlib");
+        // Unknown byte orders leave the kind unknown
+        let mut unknown = elf64(2, &[]);
+        unknown[5] = 0;
+        assert_eq!(sniff_bytes(&unknown), "application/x-elf");
+    }
+
+    fn matches(spec: &str, mime: &str) -> bool {
+        MimeFilter::parse(spec).unwrap().matches(mime)
+    }
+
+    #[test]
+    fn filters_match_types_subtypes_and_prefixes() {
+        assert!(matches("image/png", "image/png"));
+        assert!(matches("IMAGE/PNG", "image/png"));
+        assert!(!matches("image/png", "image/gif"));
+        assert!(matches("image", "image/gif"));
+        assert!(matches("image/*", "image/gif"));
+        assert!(matches("*/*", "text/plain"));
+        assert!(matches("application/x-*", "application/x-tar"));
+        assert!(!matches("application/x-*", "application/zip"));
+        assert!(MimeFilter::parse("/png").is_err());
+        assert!(MimeFilter::parse("image/").is_err());
+    }
+
+    #[test]
+    fn elf_matches_every_kind_of_elf_file() {
+        for mime in [
+            "application/x-object",
+            "application/x-executable",
+            "application/x-pie-executable",
+            "application/x-sharedlib",
+            "application/x-coredump",
+            "application/x-elf",
+        ] {
+            assert!(matches("application/x-elf", mime), "{mime}");
+        }
+        assert!(!matches("application/x-elf", "application/x-tar"));
+        assert!(!matches(
+            "application/x-sharedlib",
+            "application/x-executable"
+        ));
+    }
+}
@@ -0,0 +1,333 @@
+use crate::json;
+use crate::matcher::Matcher;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc;
+use text_colorizer::Colorize;
+
+/// How results are printed.
+#[derive(Default)]
+pub struct Output {
+    pub format: Option<Format>,
+    /// Print a JSON object with the metadata of each result instead
+    pub json: bool,
+    /// Terminate results with NUL instead of a newline
+    pub null: bool,
+    pub absolute: bool,
+    /// Print paths relative to this directory, already made absolute
+    pub relative_to: Option<PathBuf>,
+    /// Collect results and print them in this order instead of as found
+    pub sort: Option<SortKey>,
+    pub reverse: bool,
+}
+
+#[derive(Clone, Copy)]
+pub enum SortKey {
+    Path,
+    Name,
+    Size,
+    Mtime,
+}
+
+impl SortKey {
+    pub fn parse(key: &str) -> Result<SortKey, String> {
+        match key {
+            "path" => Ok(SortKey::Path),
+            "name" => Ok(SortKey::Name),
+            "size" => Ok(SortKey::Size),
+            "mtime" => Ok(SortKey::Mtime),
+            _ => Err(format!("unknown sort key `{key}`")),
+        }
+    }
+
+    /// Sorts and deduplicates `paths`, ties being sorted by path.
+    fn sort(&self, paths: &mut Vec<String>) {
+        paths.sort();
+        paths.dedup();
+        // These sorts are stable, so they keep the path order of ties
+        match self {
+            SortKey::Path => {}
+            SortKey::Name => paths
+                .sort_by_cached_key(|path| Path::new(path).file_name().map(|name| name.to_owned())),
+            SortKey::Size => paths.sort_by_cached_key(|path| {
+                fs::symlink_metadata(path).map_or(0, |metadata| metadata.len())
+            }),
+            SortKey::Mtime => paths.sort_by_cached_key(|path| {
+                fs::symlink_metadata(path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+            }),
+        }
+    }
+}
+
+enum Piece {
+    Literal(String),
+    Path,
+    Name,
+    Parent,
+    Size,
+    Mtime,
+}
+
+/// `--format` template, with `{path}`, `{name}`, `{parent}`, `{size}` (in
+/// bytes) and `{mtime}` (local time) placeholders.
+pub struct Format {
+    pieces: Vec<Piece>,
+    needs_metadata: bool,
+}
+
+impl Format {
+    pub fn new(template: &str) -> Result<Format, String> {
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            literal.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let Some(end) = rest.find('}') else {
+                break;
+            };
+            let piece = match &rest[1..end] {
+                "path" => Piece::Path,
+                "name" => Piece::Name,
+                "parent" => Piece::Parent,
+                "size" => Piece::Size,
+                "mtime" => Piece::Mtime,
+                name => return Err(format!("unknown placeholder `{{{name}}}` in format")),
+            };
+            if !literal.is_empty() {
+                pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+            }
+            pieces.push(piece);
+            rest = &rest[end + 1..];
+        }
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+
+        let needs_metadata = pieces
+            .iter()
+            .any(|piece| matches!(piece, Piece::Size | Piece::Mtime));
+        Ok(Format {
+            pieces,
+            needs_metadata,
+        })
+    }
+
+    /// Renders the result found at `found`, to be shown as `path`.
+    fn render(&self, found: &Path, path: &Path) -> String {
+        let metadata = if self.needs_metadata {
+            fs::symlink_metadata(found).ok()
+        } else {
+            None
+        };
+
+        let mut result = String::new();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Literal(text) => result.push_str(text),
+                Piece::Path => result.push_str(&path.to_string_lossy()),
+                Piece::Name => {
+                    let name = path.file_name().unwrap_or(path.as_os_str());
+                    result.push_str(&name.to_string_lossy());
+                }
+                Piece::Parent => match path.parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => {
+                        result.push_str(&parent.to_string_lossy())
+                    }
+                    _ => result.push('.'),
+                },
+                // Entries removed since they were found are left blank
+                Piece::Size => {
+                    if let Some(metadata) = &metadata {
+                        result.push_str(&metadata.len().to_string());
+                    }
+                }
+                Piece::Mtime => {
+                    if let Some(metadata) = &metadata {
+                        result.push_str(&format_time(metadata.mtime()));
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Formats a size in bytes with a binary unit, like `1.50M`.
+pub fn format_size(size: u64) -> String {
+    const UNITS: [char; 8] = ['B', 'K', 'M', 'G', 'T', 'P', 'E', 'Z'];
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size > 1024.0 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2}{}", size, UNITS[unit])
+}
+
+/// Formats seconds since the epoch as `YYYY-MM-DD HH:MM:SS` local time.
+fn format_time(seconds: i64) -> String {
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&seconds, &mut tm) }.is_null() {
+        return seconds.to_string();
+    }
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+/// `path` as seen from `base`, both absolute.
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let mut path_parts = path.components().peekable();
+    let mut base_parts = base.components().peekable();
+    while path_parts.peek().is_some() && path_parts.peek() == base_parts.peek() {
+        path_parts.next();
+        base_parts.next();
+    }
+
+    let mut relative: PathBuf = base_parts.map(|_| Component::ParentDir).collect();
+    relative.extend(path_parts);
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    relative
+}
+
+impl Output {
+    fn display_path(&self, path: &Path) -> PathBuf {
+        let absolute = || std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        match &self.relative_to {
+            Some(base) => relative_path(&absolute(), base),
+            None if self.absolute => absolute(),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// Prints every path received, coloring them when writing to a terminal.
+    /// When sorting, nothing is printed before the walk is over.
+    pub fn print(&self, rx: mpsc::Receiver<String>) {
+        let Some(key) = self.sort else {
+            self.print_all(rx.into_iter().map(|found_path| (found_path, None)));
+            return;
+        };
+
+        let mut paths: Vec<String> = rx.into_iter().collect();
+        key.sort(&mut paths);
+        if self.reverse {
+            paths.reverse();
+        }
+        self.print_all(paths.into_iter().map(|found_path| (found_path, None)));
+    }
+
+    /// Prints every path received once the walk is over, best fuzzy matches
+    /// first, highlighting the matched characters.
+    pub fn print_ranked(&self, rx: mpsc::Receiver<String>, matcher: &Matcher, root: &Path) {
+        let mut results: Vec<(i64, String, Highlight)> = rx
+            .into_iter()
+            .filter_map(|found_path| {
+                let path = Path::new(&found_path);
+                let relative = path.strip_prefix(root).unwrap_or(path);
+                let relative = relative.to_string_lossy().into_owned();
+                let (score, positions) = matcher.fuzzy_score(&relative)?;
+                Some((score, found_path, Some((relative, positions))))
+            })
+            .collect();
+        // Ties go to the shorter path
+        results.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then(a.1.len().cmp(&b.1.len()))
+                .then_with(|| a.1.cmp(&b.1))
+        });
+        self.print_all(
+            results
+                .into_iter()
+                .map(|(_, found_path, highlight)| (found_path, highlight)),
+        );
+    }
+
+    fn print_all(&self, results: impl Iterator<Item = (String, Highlight)>) {
+        let mut out = io::stdout().lock();
+        let color = self.format.is_none() && !self.json && !self.null && out.is_terminal();
+        let terminator = if self.null { '\0' } else { '\n' };
+        let mut json = self.json.then(json::Renderer::default);
+
+        for (found_path, highlight) in results {
+            let found_path = Path::new(&found_path);
+            let path = self.display_path(found_path);
+            let line = match (&self.format, &mut json) {
+                // Entries removed since they were found are left out
+                (_, Some(json)) => match json.render(found_path, &path) {
+                    Some(line) => line,
+                    None => continue,
+                },
+                (Some(format), None) => format.render(found_path, &path),
+                (None, None) => path.to_string_lossy().into_owned(),
+            };
+
+            let res = if color {
+                // The matched text is a suffix of the line unless it was made
+                // relative to another directory
+                let highlight = match &highlight {
+                    Some((text, positions)) if line.ends_with(text.as_str()) => {
+                        let offset = line.len() - text.len();
+                        positions.iter().map(|position| position + offset).collect()
+                    }
+                    _ => Vec::new(),
+                };
+                write_colored(&mut out, &line, &highlight)
+                    .and_then(|()| write!(out, "{terminator}"))
+            } else {
+                write!(out, "{line}{terminator}")
+            };
+            // Stop when the reader went away, dropping `rx` also stops the walk
+            if res.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Text a fuzzy pattern was scored against and the offsets it matched at.
+type Highlight = Option<(String, Vec<usize>)>;
+
+/// Writes `line` with its parent in blue, its file name in green and the
+/// characters at the `highlight` offsets in yellow.
+fn write_colored(out: &mut impl Write, line: &str, highlight: &[usize]) -> io::Result<()> {
+    // Split before the last slash, so `/etc` doesn't print as `//etc`
+    let name_start = line.rfind('/').unwrap_or(0);
+    let style = |offset: usize| {
+        if highlight.contains(&offset) {
+            2
+        } else if offset >= name_start {
+            1
+        } else {
+            0
+        }
+    };
+
+    let mut start = 0;
+    for (offset, _) in line.char_indices().skip(1).chain([(line.len(), ' ')]) {
+        if offset < line.len() && style(offset) == style(start) {
+            continue;
+        }
+        let run = &line[start..offset];
+        match style(start) {
+            0 => write!(out, "{}", run.blue().bold())?,
+            1 => write!(out, "{}", run.green().bold())?,
+            _ => write!(out, "{}", run.yellow().bold())?,
+        }
+        start = offset;
+    }
+    Ok(())
+}
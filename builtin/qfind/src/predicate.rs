@@ -0,0 +1,416 @@
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// Permission bits test, spelled like find's `-perm`: `644` matches the
+/// exact mode, `-644` requires all those bits and `/644` any of them.
+#[derive(Clone, Copy)]
+pub enum Perm {
+    Exact(u32),
+    All(u32),
+    Any(u32),
+}
+
+impl Perm {
+    pub fn parse(spec: &str) -> Result<Perm, String> {
+        let (build, digits): (fn(u32) -> Perm, &str) = match spec.as_bytes().first() {
+            Some(b'-') => (Perm::All, &spec[1..]),
+            Some(b'/') => (Perm::Any, &spec[1..]),
+            _ => (Perm::Exact, spec),
+        };
+        match u32::from_str_radix(digits, 8) {
+            Ok(mode) if mode <= 0o7777 => Ok(build(mode)),
+            _ => Err(format!("invalid permission mode `{spec}`")),
+        }
+    }
+
+    pub fn matches(&self, mode: u32) -> bool {
+        let mode = mode & 0o7777;
+        match *self {
+            Perm::Exact(bits) => mode == bits,
+            Perm::All(bits) => mode & bits == bits,
+            Perm::Any(bits) => bits == 0 || mode & bits != 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Access {
+    Readable,
+    Writable,
+    Executable,
+}
+
+impl Access {
+    /// Whether the current user may access `path`, as answered by access(2).
+    pub fn check(&self, path: &Path) -> bool {
+        let mode = match self {
+            Access::Readable => libc::R_OK,
+            Access::Writable => libc::W_OK,
+            Access::Executable => libc::X_OK,
+        };
+        match CString::new(path.as_os_str().as_bytes()) {
+            Ok(path) => unsafe { libc::access(path.as_ptr(), mode) == 0 },
+            Err(_) => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileKind {
+    File,
+    Directory,
+    Symlink,
+    Socket,
+    Fifo,
+    // Block and character devices, only reported when no kind is requested
+    Device,
+}
+
+impl FileKind {
+    /// `metadata` must come from `symlink_metadata` so links aren't followed.
+    pub fn from_metadata(metadata: &fs::Metadata) -> FileKind {
+        let file_type = metadata.file_type();
+        if file_type.is_symlink() {
+            FileKind::Symlink
+        } else if file_type.is_dir() {
+            FileKind::Directory
+        } else if file_type.is_socket() {
+            FileKind::Socket
+        } else if file_type.is_fifo() {
+            FileKind::Fifo
+        } else if file_type.is_file() {
+            FileKind::File
+        } else {
+            FileKind::Device
+        }
+    }
+}
+
+/// `--type` filter. Entry kinds are alternatives, while executable and empty
+/// further restrict whichever kinds were selected.
+#[derive(Clone, Default)]
+pub struct TypeFilter {
+    kinds: Vec<FileKind>,
+    executable: bool,
+    empty: bool,
+}
+
+impl TypeFilter {
+    pub fn add(&mut self, spec: &str) -> Result<(), String> {
+        for ty in spec.split(',') {
+            match ty {
+                "f" | "file" => self.kinds.push(FileKind::File),
+                "d" | "dir" | "directory" => self.kinds.push(FileKind::Directory),
+                "l" | "symlink" => self.kinds.push(FileKind::Symlink),
+                "s" | "socket" => self.kinds.push(FileKind::Socket),
+                "p" | "pipe" => self.kinds.push(FileKind::Fifo),
+                "x" | "executable" => self.executable = true,
+                "e" | "empty" => self.empty = true,
+                _ => return Err(format!("unknown file type `{ty}`")),
+            }
+        }
+        Ok(())
+    }
+
+    /// `metadata` must come from `symlink_metadata` so links aren't followed.
+    pub fn matches(&self, path: &Path, metadata: &fs::Metadata) -> bool {
+        let kind = FileKind::from_metadata(metadata);
+        if !self.matches_kind(kind) {
+            return false;
+        }
+        if self.executable && (kind != FileKind::File || metadata.mode() & 0o111 == 0) {
+            return false;
+        }
+        if self.empty {
+            return match kind {
+                FileKind::File => metadata.len() == 0,
+                FileKind::Directory => fs::read_dir(path).is_ok_and(|mut dir| dir.next().is_none()),
+                _ => false,
+            };
+        }
+        true
+    }
+
+    /// Whether `kind` is selected, leaving the executable and empty tests aside.
+    pub fn matches_kind(&self, kind: FileKind) -> bool {
+        self.kinds.is_empty() || self.kinds.contains(&kind)
+    }
+
+    /// Whether matching needs more than the kind of the entry.
+    pub fn needs_file(&self) -> bool {
+        self.executable || self.empty
+    }
+}
+
+/// `--size` bound, as in find: `+N` is larger than, `-N` smaller than and a
+/// bare `N` is N units once rounded up. Units are binary (k = 1024 bytes).
+#[derive(Clone, Copy)]
+pub enum SizeFilter {
+    Larger(u64),
+    Smaller(u64),
+    Exactly { units: u64, unit: u64 },
+}
+
+impl SizeFilter {
+    pub fn parse(spec: &str) -> Result<SizeFilter, String> {
+        let invalid = || format!("invalid size `{spec}`");
+        let (sign, rest) = match spec.as_bytes().first() {
+            Some(b'+' | b'-') => (Some(spec.as_bytes()[0]), &spec[1..]),
+            _ => (None, spec),
+        };
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let units: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+        let unit = match rest[digits..]
+            .to_ascii_lowercase()
+            .trim_end_matches(['i', 'b'])
+        {
+            "" => 1,
+            "k" => 1 << 10,
+            "m" => 1 << 20,
+            "g" => 1 << 30,
+            "t" => 1 << 40,
+            _ => return Err(invalid()),
+        };
+        let bytes = units.checked_mul(unit).ok_or_else(invalid)?;
+
+        Ok(match sign {
+            Some(b'+') => SizeFilter::Larger(bytes),
+            Some(_) => SizeFilter::Smaller(bytes),
+            None => SizeFilter::Exactly { units, unit },
+        })
+    }
+
+    pub fn matches(&self, size: u64) -> bool {
+        match *self {
+            SizeFilter::Larger(bytes) => size > bytes,
+            SizeFilter::Smaller(bytes) => size < bytes,
+            SizeFilter::Exactly { units, unit } => size.div_ceil(unit) == units,
+        }
+    }
+}
+
+/// Bound on the modification time.
+#[derive(Clone, Copy)]
+pub enum TimeFilter {
+    After(SystemTime),
+    Before(SystemTime),
+}
+
+impl TimeFilter {
+    pub fn matches(&self, metadata: &fs::Metadata) -> bool {
+        metadata
+            .modified()
+            .is_ok_and(|modified| self.matches_time(modified))
+    }
+
+    pub fn matches_time(&self, modified: SystemTime) -> bool {
+        match *self {
+            TimeFilter::After(time) => modified > time,
+            TimeFilter::Before(time) => modified < time,
+        }
+    }
+}
+
+/// Parses either a duration back from now (`90s`, `15min`, `2d`, `1w`) or a
+/// local date (`2024-01-01`, `2024-01-01 12:30[:00]`).
+pub fn parse_time(spec: &str) -> Result<SystemTime, String> {
+    parse_duration(spec)
+        .and_then(|duration| SystemTime::now().checked_sub(duration))
+        .or_else(|| parse_date(spec))
+        .ok_or_else(|| format!("invalid time `{spec}`"))
+}
+
+fn parse_duration(spec: &str) -> Option<Duration> {
+    let digits = spec.len() - spec.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let amount: u64 = spec[..digits].parse().ok()?;
+    let seconds = match &spec[digits..] {
+        "s" | "sec" | "secs" | "second" | "seconds" => 1,
+        "m" | "min" | "mins" | "minute" | "minutes" => 60,
+        "h" | "hour" | "hours" => 60 * 60,
+        "d" | "day" | "days" => 24 * 60 * 60,
+        "w" | "week" | "weeks" => 7 * 24 * 60 * 60,
+        "y" | "year" | "years" => 365 * 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(Duration::from_secs(amount.checked_mul(seconds)?))
+}
+
+fn parse_date(spec: &str) -> Option<SystemTime> {
+    let (date, time) = spec.split_once([' ', 'T']).unwrap_or((spec, "00:00"));
+    let date: Vec<i32> = date
+        .split('-')
+        .map(|n| n.parse().ok())
+        .collect::<Option<_>>()?;
+    let time: Vec<i32> = time
+        .split(':')
+        .map(|n| n.parse().ok())
+        .collect::<Option<_>>()?;
+    let (&[year, month, day], &[hour, minute, ..]) = (&date[..], &time[..]) else {
+        return None;
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Let mktime(3) apply the local timezone and DST rules
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    tm.tm_year = year - 1900;
+    tm.tm_mon = month - 1;
+    tm.tm_mday = day;
+    tm.tm_hour = hour;
+    tm.tm_min = minute;
+    tm.tm_sec = time.get(2).copied().unwrap_or(0);
+    tm.tm_isdst = -1;
+    let seconds = unsafe { libc::mktime(&mut tm) };
+    if seconds == -1 {
+        return None;
+    }
+
+    if seconds >= 0 {
+        SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(seconds as u64))
+    } else {
+        SystemTime::UNIX_EPOCH.checked_sub(Duration::from_secs(seconds.unsigned_abs()))
+    }
+}
+
+/// Whether the file `name` ends with `.extension`, ignoring case, where
+/// `extension` is lowercase and may have several parts like `tar.gz`.
+pub fn has_extension(name: &str, extension: &str) -> bool {
+    name.to_lowercase()
+        .strip_suffix(extension)
+        .and_then(|stem| stem.strip_suffix('.'))
+        .is_some_and(|stem| !stem.is_empty())
+}
+
+/// Whether `metadata`, from `symlink_metadata`, is that of a symlink whose
+/// target doesn't exist.
+pub fn is_broken_link(path: &Path, metadata: &fs::Metadata) -> bool {
+    metadata.is_symlink()
+        && fs::metadata(path).is_err_and(|err| {
+            err.kind() == io::ErrorKind::NotFound
+                || matches!(err.raw_os_error(), Some(libc::ELOOP | libc::ENOTDIR))
+        })
+}
+
+/// `--owner user:group`, where either side may be empty, a name or an id.
+#[derive(Clone, Copy)]
+pub struct Owner {
+    uid: Option<u32>,
+    gid: Option<u32>,
+}
+
+impl Owner {
+    pub fn parse(spec: &str) -> Result<Owner, String> {
+        let (user, group) = spec.split_once(':').unwrap_or((spec, ""));
+        let uid = match user {
+            "" => None,
+            user => Some(
+                user.parse()
+                    .ok()
+                    .or_else(|| get_user_id(user))
+                    .ok_or_else(|| format!("unknown user `{user}`"))?,
+            ),
+        };
+        let gid = match group {
+            "" => None,
+            group => Some(
+                group
+                    .parse()
+                    .ok()
+                    .or_else(|| get_group_id(group))
+                    .ok_or_else(|| format!("unknown group `{group}`"))?,
+            ),
+        };
+        Ok(Owner { uid, gid })
+    }
+
+    pub fn matches(&self, metadata: &fs::Metadata) -> bool {
+        self.uid.is_none_or(|uid| metadata.uid() == uid)
+            && self.gid.is_none_or(|gid| metadata.gid() == gid)
+    }
+}
+
+/// Calls one of the reentrant passwd or group lookups, `lookup` taking the
+/// entry to fill, the buffer for its strings and where to store the result.
+/// The buffer starts at the size `sysconf(size_name)` suggests and grows
+/// while too small. `read` extracts what's needed before the buffer goes.
+fn lookup_entry<T, R>(
+    size_name: libc::c_int,
+    mut lookup: impl FnMut(*mut T, &mut [u8], *mut *mut T) -> libc::c_int,
+    read: impl FnOnce(&T) -> R,
+) -> Option<R> {
+    const MAX_SIZE: usize = 1 << 20;
+    let size = unsafe { libc::sysconf(size_name) };
+    let mut buf = vec![
+        0u8;
+        usize::try_from(size)
+            .ok()
+            .filter(|&size| size > 0)
+            .unwrap_or(1024)
+    ];
+    loop {
+        // Only used for passwd and group, which are plain integers and pointers
+        let mut entry: T = unsafe { std::mem::zeroed() };
+        let mut ptr: *mut T = std::ptr::null_mut();
+        match lookup(&mut entry, &mut buf, &mut ptr) {
+            0 if !ptr.is_null() => return Some(read(unsafe { &*ptr })),
+            libc::ERANGE if buf.len() < MAX_SIZE => buf.resize(buf.len() * 2, 0),
+            _ => return None,
+        }
+    }
+}
+
+fn get_user_id(name: &str) -> Option<u32> {
+    let name = CString::new(name).ok()?;
+    lookup_entry(
+        libc::_SC_GETPW_R_SIZE_MAX,
+        |pwd, buf, ptr| unsafe {
+            libc::getpwnam_r(name.as_ptr(), pwd, buf.as_mut_ptr().cast(), buf.len(), ptr)
+        },
+        |pwd: &libc::passwd| pwd.pw_uid,
+    )
+}
+
+fn get_group_id(name: &str) -> Option<u32> {
+    let name = CString::new(name).ok()?;
+    lookup_entry(
+        libc::_SC_GETGR_R_SIZE_MAX,
+        |grp, buf, ptr| unsafe {
+            libc::getgrnam_r(name.as_ptr(), grp, buf.as_mut_ptr().cast(), buf.len(), ptr)
+        },
+        |grp: &libc::group| grp.gr_gid,
+    )
+}
+
+pub fn get_user_name(uid: u32) -> Option<String> {
+    lookup_entry(
+        libc::_SC_GETPW_R_SIZE_MAX,
+        |pwd, buf, ptr| unsafe {
+            libc::getpwuid_r(uid, pwd, buf.as_mut_ptr().cast(), buf.len(), ptr)
+        },
+        |pwd: &libc::passwd| {
+            unsafe { CStr::from_ptr(pwd.pw_name) }
+                .to_string_lossy()
+                .into_owned()
+        },
+    )
+}
+
+pub fn get_group_name(gid: u32) -> Option<String> {
+    lookup_entry(
+        libc::_SC_GETGR_R_SIZE_MAX,
+        |grp, buf, ptr| unsafe {
+            libc::getgrgid_r(gid, grp, buf.as_mut_ptr().cast(), buf.len(), ptr)
+        },
+        |grp: &libc::group| {
+            unsafe { CStr::from_ptr(grp.gr_name) }
+                .to_string_lossy()
+                .into_owned()
+        },
+    )
+}
@@ -0,0 +1,268 @@
+use crate::ignore::Ignore;
+use crate::watch::Watcher;
+use crate::Options;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use text_colorizer::Colorize;
+
+/// Directories waiting to be read, shared by every worker. `pending` counts
+/// both queued directories and the ones being read, so the walk is over once
+/// it drops to zero.
+struct Queue {
+    state: Mutex<QueueState>,
+    ready: Condvar,
+}
+
+struct QueueState {
+    dirs: Vec<Job>,
+    pending: usize,
+}
+
+/// Directory to read, `depth` levels below the start directory.
+pub struct Job {
+    pub dir: PathBuf,
+    pub depth: usize,
+    /// Ignore rules inherited from the parent directories
+    pub ignore: Option<Arc<Ignore>>,
+}
+
+impl Queue {
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        // A panicking worker must not wedge the others
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn push(&self, job: Job) {
+        let mut state = self.lock();
+        state.dirs.push(job);
+        state.pending += 1;
+        self.ready.notify_one();
+    }
+
+    /// Blocks until there is a directory to read, or returns `None` once
+    /// there is nothing left to do.
+    fn pop(&self) -> Option<Job> {
+        let mut state = self.lock();
+        loop {
+            if let Some(dir) = state.dirs.pop() {
+                return Some(dir);
+            }
+            if state.pending == 0 {
+                return None;
+            }
+            state = self
+                .ready
+                .wait(state)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+    }
+
+    fn done(&self) {
+        let mut state = self.lock();
+        state.pending -= 1;
+        if state.pending == 0 {
+            self.ready.notify_all();
+        }
+    }
+}
+
+/// Marks a popped directory as done even if reading it panics.
+struct Done<'a>(&'a Queue);
+
+impl Drop for Done<'_> {
+    fn drop(&mut self) {
+        self.0.done();
+    }
+}
+
+pub struct Walk {
+    workers: Vec<thread::JoinHandle<()>>,
+    start_failed: Arc<AtomicBool>,
+}
+
+impl Walk {
+    /// Waits for the walk to finish, returning whether the start directory
+    /// could be read. Other entries that couldn't be read were skipped and
+    /// reported to `options.errors`.
+    pub fn join(self) -> bool {
+        for worker in self.workers {
+            worker.join().expect("The thread failed");
+        }
+        !self.start_failed.load(Ordering::Relaxed)
+    }
+}
+
+/// Walks `options.root` on `options.jobs` threads, sending matches on `tx`
+/// and watching the directories read with `watcher`.
+pub fn start(
+    options: Arc<Options>,
+    tx: mpsc::Sender<String>,
+    watcher: Option<Arc<Watcher>>,
+) -> Walk {
+    let ignore = if options.ignore.any() {
+        Ignore::ancestors(&options.root, options.ignore)
+    } else {
+        None
+    };
+    let job = Job {
+        dir: options.root.clone(),
+        depth: 0,
+        ignore,
+    };
+    start_at(options, job, tx, watcher)
+}
+
+/// Walks the directory of `job` only.
+pub fn start_at(
+    options: Arc<Options>,
+    job: Job,
+    tx: mpsc::Sender<String>,
+    watcher: Option<Arc<Watcher>>,
+) -> Walk {
+    let queue = Arc::new(Queue {
+        state: Mutex::new(QueueState {
+            dirs: Vec::new(),
+            pending: 0,
+        }),
+        ready: Condvar::new(),
+    });
+    let start = job.dir.clone();
+    queue.push(job);
+
+    let start_failed = Arc::new(AtomicBool::new(false));
+    let workers = (0..options.jobs.max(1))
+        .map(|_| {
+            let options = Arc::clone(&options);
+            let queue = Arc::clone(&queue);
+            let start = start.clone();
+            let start_failed = Arc::clone(&start_failed);
+            let tx = tx.clone();
+            let watcher = watcher.clone();
+            thread::spawn(move || {
+                while let Some(job) = queue.pop() {
+                    let _done = Done(&queue);
+                    let watcher = watcher.as_deref();
+                    match visit_dir(&job, &options, &queue, &tx, watcher) {
+                        // Nothing can be found without the start directory
+                        Err(err) if job.dir == start => {
+                            eprintln!("{} {}: {}", "Error:".red().bold(), start.display(), err);
+                            start_failed.store(true, Ordering::Relaxed);
+                        }
+                        Err(err) => options.errors.report(&job.dir, &err),
+                        Ok(()) => {}
+                    }
+                }
+            })
+        })
+        .collect();
+
+    Walk {
+        workers,
+        start_failed,
+    }
+}
+
+fn visit_dir(
+    job: &Job,
+    options: &Options,
+    queue: &Queue,
+    tx: &mpsc::Sender<String>,
+    watcher: Option<&Watcher>,
+) -> std::io::Result<()> {
+    let dir = &job.dir;
+    // Depth of the entries of `dir`, the start directory itself being 0
+    let depth = job.depth + 1;
+    if !options.depth_allows(depth) {
+        return Ok(());
+    }
+
+    let ignore = if options.ignore.any() {
+        Ignore::child(job.ignore.clone(), dir, options.ignore)
+    } else {
+        None
+    };
+    // Watch before reading, so no entry created meanwhile goes unnoticed
+    if let Some(watcher) = watcher {
+        if let Err(err) = watcher.add(dir, depth, ignore.clone()) {
+            options.errors.report(dir, &err);
+        }
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let visit = match visit_entry(&path, depth, &ignore, options) {
+            Ok(visit) => visit,
+            Err(err) => {
+                options.errors.report(&path, &err);
+                continue;
+            }
+        };
+
+        if visit.matched {
+            // The receiver only goes away once output stops, so stop walking
+            if tx.send(path.display().to_string()).is_err() {
+                return Ok(());
+            }
+        }
+        if let Some(subdir) = visit.subdir {
+            queue.push(subdir);
+        }
+    }
+    Ok(())
+}
+
+/// What to do with an entry found while walking.
+#[derive(Default)]
+pub struct Visit {
+    pub matched: bool,
+    /// Set for directories to descend into
+    pub subdir: Option<Job>,
+}
+
+/// Tests the entry at `path`, `depth` levels below the start directory,
+/// `ignore` holding the rules of its directory.
+pub fn visit_entry(
+    path: &Path,
+    depth: usize,
+    ignore: &Option<Arc<Ignore>>,
+    options: &Options,
+) -> std::io::Result<Visit> {
+    if !options.depth_allows(depth) || options.is_excluded(path) {
+        return Ok(Visit::default());
+    }
+
+    let metadata = fs::symlink_metadata(path)?;
+
+    if options.ignore.vcs && metadata.is_dir() && path.file_name() == Some(".git".as_ref())
+        || ignore
+            .as_ref()
+            .is_some_and(|ignore| ignore.is_ignored(path, metadata.is_dir()))
+    {
+        return Ok(Visit::default());
+    }
+
+    let mut visit = Visit {
+        matched: depth >= options.min_depth && options.is_match(path, &metadata),
+        subdir: None,
+    };
+
+    if metadata.is_dir() {
+        if options.root_dev.is_some_and(|dev| metadata.dev() != dev) || options.is_pruned(path) {
+            return Ok(visit);
+        }
+        let dir_name = path.file_name().unwrap().to_string_lossy();
+        if (options.hidden_folders || !dir_name.starts_with('.')) && options.depth_allows(depth + 1)
+        {
+            visit.subdir = Some(Job {
+                dir: path.to_path_buf(),
+                depth,
+                ignore: ignore.clone(),
+            });
+        }
+    }
+    Ok(visit)
+}
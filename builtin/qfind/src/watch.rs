@@ -0,0 +1,158 @@
+use crate::ignore::Ignore;
+use crate::walk;
+use crate::Options;
+use std::collections::HashMap;
+use std::ffi::{CString, OsStr};
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use text_colorizer::Colorize;
+
+const EVENTS: u32 =
+    libc::IN_CREATE | libc::IN_MOVED_TO | libc::IN_MOVED_FROM | libc::IN_CLOSE_WRITE;
+
+/// Watched directory, with what its entries need to be tested.
+#[derive(Clone)]
+struct Watched {
+    dir: PathBuf,
+    /// Depth of the entries of `dir`
+    depth: usize,
+    /// Ignore rules for the entries of `dir`
+    ignore: Option<Arc<Ignore>>,
+}
+
+/// inotify instance watching the walked directories.
+pub struct Watcher {
+    fd: OwnedFd,
+    dirs: Mutex<HashMap<i32, Watched>>,
+}
+
+impl Watcher {
+    pub fn new() -> io::Result<Watcher> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Watcher {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            dirs: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Watches `dir`, whose entries are `depth` levels down and follow the
+    /// `ignore` rules.
+    pub fn add(&self, dir: &Path, depth: usize, ignore: Option<Arc<Ignore>>) -> io::Result<()> {
+        let path = CString::new(dir.as_os_str().as_bytes())?;
+        let mask = EVENTS | libc::IN_ONLYDIR | libc::IN_DONT_FOLLOW;
+        let wd = unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), path.as_ptr(), mask) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let watched = Watched {
+            dir: dir.to_path_buf(),
+            depth,
+            ignore,
+        };
+        self.lock().insert(wd, watched);
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<i32, Watched>> {
+        self.dirs.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Stops watching `dir` and everything below, which moved away.
+    fn remove(&self, dir: &Path) {
+        let mut dirs = self.lock();
+        dirs.retain(|&wd, watched| {
+            if !watched.dir.starts_with(dir) {
+                return true;
+            }
+            unsafe { libc::inotify_rm_watch(self.fd.as_raw_fd(), wd) };
+            false
+        });
+    }
+
+    /// Sends matching entries as they're created, modified or moved in, until
+    /// the receiver goes away. Regular files are only reported once written,
+    /// unless they're hard links. Errors met walking new directories are
+    /// summarized after each of those walks.
+    pub fn run(
+        self: &Arc<Self>,
+        options: &Arc<Options>,
+        tx: &mpsc::Sender<String>,
+    ) -> io::Result<()> {
+        const HEADER: usize = mem::size_of::<libc::inotify_event>();
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let len =
+                unsafe { libc::read(self.fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+            if len < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err);
+            }
+
+            let mut offset = 0;
+            while offset + HEADER <= len as usize {
+                let event: libc::inotify_event =
+                    unsafe { std::ptr::read_unaligned(buf[offset..].as_ptr().cast()) };
+                let name = &buf[offset + HEADER..offset + HEADER + event.len as usize];
+                let name = OsStr::from_bytes(name.split(|&b| b == 0).next().unwrap_or(name));
+                offset += HEADER + event.len as usize;
+
+                if event.mask & libc::IN_Q_OVERFLOW != 0 {
+                    eprintln!(
+                        "{} too many events, some were lost",
+                        "Warning:".yellow().bold()
+                    );
+                    continue;
+                }
+                if event.mask & libc::IN_IGNORED != 0 {
+                    self.lock().remove(&event.wd);
+                    continue;
+                }
+                let Some(watched) = self.lock().get(&event.wd).cloned() else {
+                    continue;
+                };
+                let path = watched.dir.join(name);
+
+                if event.mask & libc::IN_MOVED_FROM != 0 {
+                    if event.mask & libc::IN_ISDIR != 0 {
+                        self.remove(&path);
+                    }
+                    continue;
+                }
+                // Files being created are reported once closed after writing,
+                // but new hard links to existing files are complete already
+                if event.mask & libc::IN_CREATE != 0
+                    && std::fs::symlink_metadata(&path)
+                        .is_ok_and(|metadata| metadata.is_file() && metadata.nlink() == 1)
+                {
+                    continue;
+                }
+
+                // Entries may already be gone again
+                let Ok(visit) = walk::visit_entry(&path, watched.depth, &watched.ignore, options)
+                else {
+                    continue;
+                };
+                if visit.matched && tx.send(path.display().to_string()).is_err() {
+                    return Ok(());
+                }
+                // Report and watch the contents of new directories
+                if let Some(subdir) = visit.subdir {
+                    let watcher = Some(Arc::clone(self));
+                    walk::start_at(Arc::clone(options), subdir, tx.clone(), watcher).join();
+                    options.errors.print_summary();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Fresh directory holding:
+///
+/// ```text
+/// d/a.o  d/b.txt  d/e/c.o  keep.txt  top.o
+/// ```
+fn tree(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("qfind-action-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("d/e")).unwrap();
+    for file in ["d/a.o", "d/b.txt", "d/e/c.o", "keep.txt", "top.o"] {
+        fs::write(dir.join(file), file).unwrap();
+    }
+    dir
+}
+
+fn qfind(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_qfind"))
+        .args(args)
+        .current_dir(dir)
+        .env("XDG_CONFIG_HOME", dir)
+        .output()
+        .unwrap()
+}
+
+/// Every entry below `dir`, sorted.
+fn entries(dir: &Path) -> Vec<String> {
+    let mut entries = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        for entry in fs::read_dir(&current).unwrap() {
+            let path = entry.unwrap().path();
+            entries.push(path.strip_prefix(dir).unwrap().display().to_string());
+            if path.is_dir() {
+                dirs.push(path);
+            }
+        }
+    }
+    entries.sort();
+    entries
+}
+
+#[test]
+fn delete_keeps_unmatched_children_of_matched_directories() {
+    let dir = tree("delete-dir");
+    let output = qfind(&dir, &["d", ".", "-t", "d", "--delete", "-y"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        entries(&dir),
+        ["d", "d/a.o", "d/b.txt", "d/e", "d/e/c.o", "keep.txt", "top.o"]
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn delete_removes_matches_only() {
+    let dir = tree("delete-files");
+    let output = qfind(&dir, &["-g", "*.o", ".", "--delete", "-y"]);
+    assert!(output.status.success());
+    assert_eq!(entries(&dir), ["d", "d/b.txt", "d/e", "keep.txt"]);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn delete_removes_directories_emptied_by_matches() {
+    let dir = tree("delete-emptied");
+    let output = qfind(
+        &dir,
+        &["--delete", "-y", ".", "-name", "e", "-o", "-name", "c.o"],
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        entries(&dir),
+        ["d", "d/a.o", "d/b.txt", "keep.txt", "top.o"]
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn move_refuses_directories_with_unmatched_contents() {
+    let dir = tree("move");
+    fs::create_dir(dir.join("dest")).unwrap();
+    let output = qfind(
+        &dir,
+        &[
+            "-g",
+            "{d,*.o}",
+            ".",
+            "--exclude",
+            "dest",
+            "--move-to",
+            "dest",
+            "-y",
+        ],
+    );
+    assert!(!output.status.success());
+    assert_eq!(
+        entries(&dir),
+        [
+            "d",
+            "d/b.txt",
+            "d/e",
+            "dest",
+            "dest/a.o",
+            "dest/c.o",
+            "dest/top.o",
+            "keep.txt"
+        ]
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
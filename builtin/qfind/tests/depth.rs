@@ -0,0 +1,68 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Runs qfind over the `test/depth` fixture, returning the sorted paths it
+/// reports relative to the fixture:
+///
+/// ```text
+/// top.txt  .hidden/one.txt  a/one.txt  a/b/two.txt  a/b/c/three.txt
+/// ```
+fn qfind(args: &[&str]) -> Vec<String> {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("test/depth");
+    let output = Command::new(env!("CARGO_BIN_EXE_qfind"))
+        .arg("")
+        .arg(&fixture)
+        .args(args)
+        .env("XDG_CONFIG_HOME", &fixture)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let mut paths: Vec<String> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let path = Path::new(line).strip_prefix(&fixture).unwrap();
+            path.display().to_string()
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn max_depth_limits_files_and_directories() {
+    assert_eq!(qfind(&["--max-depth", "1"]), [".hidden", "a", "top.txt"]);
+    assert_eq!(
+        qfind(&["--max-depth", "2"]),
+        [".hidden", "a", "a/b", "a/one.txt", "top.txt"]
+    );
+    assert!(qfind(&["--max-depth", "0"]).is_empty());
+}
+
+#[test]
+fn max_depth_applies_to_hidden_directories() {
+    assert_eq!(
+        qfind(&["-H", "--max-depth", "2"]),
+        [
+            ".hidden",
+            ".hidden/one.txt",
+            "a",
+            "a/b",
+            "a/one.txt",
+            "top.txt"
+        ]
+    );
+}
+
+#[test]
+fn min_depth_skips_shallow_entries() {
+    assert_eq!(
+        qfind(&["--min-depth", "3"]),
+        ["a/b/c", "a/b/c/three.txt", "a/b/two.txt"]
+    );
+    assert_eq!(
+        qfind(&["-H", "--min-depth", "2", "--max-depth", "3", "--type", "f"]),
+        [".hidden/one.txt", "a/b/two.txt", "a/one.txt"]
+    );
+}
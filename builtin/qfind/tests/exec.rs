@@ -0,0 +1,24 @@
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn exec_prints_the_output_of_each_command_whole() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("test/depth");
+    let output = Command::new(env!("CARGO_BIN_EXE_qfind"))
+        .arg("")
+        .arg(&fixture)
+        .args(["-H", "--type", "f", "-j", "4", "-x", "sh", "-c"])
+        .arg("for i in 1 2 3 4 5; do echo \"$1\"; sleep 0.01; done")
+        .args(["sh", "{/}", ";"])
+        .env("XDG_CONFIG_HOME", &fixture)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 25);
+    for chunk in lines.chunks(5) {
+        assert!(chunk.iter().all(|line| *line == chunk[0]), "{stdout}");
+    }
+}
@@ -0,0 +1,1161 @@
+//! Read-only access to a local git repository: just enough of the object
+//! store, refs and index to list the blobs of a revision and read them.
+
+use flate2::bufread::ZlibDecoder;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use text_colorizer::Colorize;
+
+pub type Oid = [u8; 20];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Kind {
+    Commit,
+    Tree,
+    Blob,
+    Tag,
+}
+
+impl Kind {
+    fn from_name(name: &[u8]) -> io::Result<Kind> {
+        match name {
+            b"commit" => Ok(Kind::Commit),
+            b"tree" => Ok(Kind::Tree),
+            b"blob" => Ok(Kind::Blob),
+            b"tag" => Ok(Kind::Tag),
+            _ => Err(corrupt("unknown object type")),
+        }
+    }
+}
+
+struct Pack {
+    idx: Vec<u8>,
+    path: PathBuf,
+}
+
+impl Pack {
+    const FANOUT: usize = 8;
+
+    fn open(idx_path: &Path) -> io::Result<Pack> {
+        let idx = fs::read(idx_path)?;
+        if idx.len() < Self::FANOUT + 256 * 4 || idx[..8] != [0xff, b't', b'O', b'c', 0, 0, 0, 2] {
+            return Err(corrupt("unsupported pack index version"));
+        }
+        Ok(Pack {
+            idx,
+            path: idx_path.with_extension("pack"),
+        })
+    }
+
+    fn fanout(&self, byte: usize) -> usize {
+        if byte == 0 {
+            0
+        } else {
+            read_u32(&self.idx, Self::FANOUT + (byte - 1) * 4) as usize
+        }
+    }
+
+    fn count(&self) -> usize {
+        read_u32(&self.idx, Self::FANOUT + 255 * 4) as usize
+    }
+
+    fn oid(&self, n: usize) -> &[u8] {
+        let start = Self::FANOUT + 256 * 4 + n * 20;
+        &self.idx[start..start + 20]
+    }
+
+    /// Index range of the objects whose id starts with `prefix`.
+    fn range(&self, prefix: &[u8]) -> std::ops::Range<usize> {
+        let first = prefix[0] as usize;
+        let bucket_end = self.fanout(first + 1);
+        let (mut lo, mut hi) = (self.fanout(first), bucket_end);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if self.oid(mid) < prefix {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        let mut end = lo;
+        while end < bucket_end && self.oid(end).starts_with(prefix) {
+            end += 1;
+        }
+        lo..end
+    }
+
+    fn offset(&self, n: usize) -> u64 {
+        let count = self.count();
+        let offsets = Self::FANOUT + 256 * 4 + count * 24;
+        let offset = read_u32(&self.idx, offsets + n * 4);
+        if offset & 0x8000_0000 == 0 {
+            offset as u64
+        } else {
+            let large = offsets + count * 4 + (offset & 0x7fff_ffff) as usize * 8;
+            (read_u32(&self.idx, large) as u64) << 32 | read_u32(&self.idx, large + 4) as u64
+        }
+    }
+}
+
+pub struct Repository {
+    git_dir: PathBuf,
+    common_dir: PathBuf,
+    work_tree: PathBuf,
+    packs: Vec<Pack>,
+}
+
+impl Repository {
+    /// Finds the repository containing `path` by walking up its ancestors.
+    /// `path` needn't exist anymore, as long as some ancestor does.
+    pub fn discover(path: &Path) -> io::Result<Repository> {
+        let path = resolve(path)?;
+        for dir in path.ancestors() {
+            let dot_git = dir.join(".git");
+            if dot_git.is_dir() {
+                return Repository::open(dot_git, dir.to_path_buf());
+            }
+            if dot_git.is_file() {
+                // Worktrees and submodules point at their real git dir
+                let content = fs::read_to_string(&dot_git)?;
+                let git_dir = content
+                    .strip_prefix("gitdir:")
+                    .ok_or_else(|| corrupt("malformed .git file"))?
+                    .trim();
+                return Repository::open(dir.join(git_dir), dir.to_path_buf());
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "not inside a git repository",
+        ))
+    }
+
+    fn open(git_dir: PathBuf, work_tree: PathBuf) -> io::Result<Repository> {
+        let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+            Ok(common_dir) => git_dir.join(common_dir.trim()),
+            Err(_) => git_dir.clone(),
+        };
+
+        let mut packs = Vec::new();
+        if let Ok(entries) = fs::read_dir(common_dir.join("objects/pack")) {
+            for entry in entries {
+                let path = entry?.path();
+                if path.extension().is_some_and(|ext| ext == "idx") {
+                    // Objects of a pack we can't read are reported missing if
+                    // ever needed, the others are still usable
+                    match Pack::open(&path) {
+                        Ok(pack) => packs.push(pack),
+                        Err(err) => eprintln!(
+                            "{}: skipping {}: {}",
+                            "Warning".yellow().bold(),
+                            path.display(),
+                            err
+                        ),
+                    }
+                }
+            }
+        }
+
+        Ok(Repository {
+            git_dir,
+            common_dir,
+            work_tree,
+            packs,
+        })
+    }
+
+    /// Path of `path` relative to the work tree, `/`-separated. Like in
+    /// `discover`, it may have been deleted from the work tree.
+    pub fn relative_path(&self, path: &Path) -> io::Result<String> {
+        let path = resolve(path)?;
+        let relative = path
+            .strip_prefix(&self.work_tree)
+            .map_err(|_| corrupt("path is outside the repository"))?;
+        Ok(relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"))
+    }
+
+    /// Resolves a revision like `HEAD`, `main~3`, `v1.0^2` or an abbreviated
+    /// object id to the tree it points at.
+    pub fn resolve_tree(&self, rev: &str) -> io::Result<Oid> {
+        let split = rev.find(['~', '^']).unwrap_or(rev.len());
+        let (base, mut suffix) = rev.split_at(split);
+        let mut oid = self.resolve_name(base)?;
+
+        while let Some(op) = suffix.chars().next() {
+            suffix = &suffix[1..];
+            let digits = suffix.len()
+                - suffix
+                    .trim_start_matches(|c: char| c.is_ascii_digit())
+                    .len();
+            let n = match &suffix[..digits] {
+                "" => 1,
+                n => n.parse().map_err(|_| bad_rev(rev))?,
+            };
+            suffix = &suffix[digits..];
+
+            oid = self.peel(oid, Kind::Commit)?;
+            match op {
+                '~' => {
+                    for _ in 0..n {
+                        oid = *self.parents(&oid)?.first().ok_or_else(|| bad_rev(rev))?;
+                    }
+                }
+                '^' if n > 0 => {
+                    oid = *self.parents(&oid)?.get(n - 1).ok_or_else(|| bad_rev(rev))?;
+                }
+                '^' => {}
+                _ => return Err(bad_rev(rev)),
+            }
+        }
+
+        self.peel(oid, Kind::Tree)
+    }
+
+    fn resolve_name(&self, name: &str) -> io::Result<Oid> {
+        if name.is_empty() || name == "@" {
+            return self.resolve_ref("HEAD");
+        }
+
+        let candidates = [
+            name.to_string(),
+            format!("refs/{name}"),
+            format!("refs/tags/{name}"),
+            format!("refs/heads/{name}"),
+            format!("refs/remotes/{name}"),
+            format!("refs/remotes/{name}/HEAD"),
+        ];
+        for candidate in &candidates {
+            if let Ok(oid) = self.resolve_ref(candidate) {
+                return Ok(oid);
+            }
+        }
+
+        if name.len() >= 4 && name.len() <= 40 && name.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return self.expand_oid(&name.to_ascii_lowercase());
+        }
+
+        Err(bad_rev(name))
+    }
+
+    fn resolve_ref(&self, name: &str) -> io::Result<Oid> {
+        let dir = if name.starts_with("refs/") {
+            &self.common_dir
+        } else {
+            &self.git_dir
+        };
+
+        if let Ok(content) = fs::read_to_string(dir.join(name)) {
+            let content = content.trim();
+            return match content.strip_prefix("ref:") {
+                Some(target) => self.resolve_ref(target.trim()),
+                None => parse_hex(content),
+            };
+        }
+
+        let packed = fs::read_to_string(self.common_dir.join("packed-refs"))?;
+        packed
+            .lines()
+            .filter(|line| !line.starts_with(['#', '^']))
+            .find_map(|line| match line.split_once(' ') {
+                Some((oid, refname)) if refname == name => Some(parse_hex(oid)),
+                _ => None,
+            })
+            .unwrap_or_else(|| Err(bad_rev(name)))
+    }
+
+    fn expand_oid(&self, hex: &str) -> io::Result<Oid> {
+        let mut matches: Vec<Oid> = Vec::new();
+
+        if let Ok(entries) = fs::read_dir(self.common_dir.join("objects").join(&hex[..2])) {
+            for entry in entries {
+                let name = entry?.file_name();
+                let full = format!("{}{}", &hex[..2], name.to_string_lossy());
+                if full.len() == 40 && full.starts_with(hex) {
+                    matches.push(parse_hex(&full)?);
+                }
+            }
+        }
+
+        // Odd-length prefixes are compared on the whole bytes first
+        let prefix = parse_hex_prefix(&hex[..hex.len() & !1]);
+        for pack in &self.packs {
+            for n in pack.range(&prefix) {
+                let mut oid = [0; 20];
+                oid.copy_from_slice(pack.oid(n));
+                if to_hex(&oid).starts_with(hex) && !matches.contains(&oid) {
+                    matches.push(oid);
+                }
+            }
+        }
+
+        match matches.as_slice() {
+            [oid] => Ok(*oid),
+            [] => Err(bad_rev(hex)),
+            _ => Err(corrupt(&format!("short object id {hex} is ambiguous"))),
+        }
+    }
+
+    fn parents(&self, commit: &Oid) -> io::Result<Vec<Oid>> {
+        let (_, data) = self.read(commit)?;
+        header_lines(&data)
+            .filter_map(|line| line.strip_prefix("parent "))
+            .map(parse_hex)
+            .collect()
+    }
+
+    /// Follows tags and commits until an object of kind `target` is reached.
+    fn peel(&self, mut oid: Oid, target: Kind) -> io::Result<Oid> {
+        loop {
+            let (kind, data) = self.read(&oid)?;
+            let field = match kind {
+                _ if kind == target => return Ok(oid),
+                Kind::Tag => "object ",
+                Kind::Commit if target == Kind::Tree => "tree ",
+                _ => return Err(corrupt(&format!("{} is not a {target:?}", to_hex(&oid)))),
+            };
+            let line = header_lines(&data)
+                .find(|line| line.starts_with(field))
+                .ok_or_else(|| corrupt("malformed commit or tag"))?;
+            oid = parse_hex(&line[field.len()..])?;
+        }
+    }
+
+    /// Lists every regular file under `prefix` in the given tree.
+    pub fn tree_blobs(&self, tree: &Oid, prefix: &str) -> io::Result<Vec<(String, Oid)>> {
+        let mut tree = *tree;
+        let mut path = String::new();
+
+        for component in prefix.split('/').filter(|c| !c.is_empty()) {
+            let entry = self
+                .tree_entries(&tree)?
+                .into_iter()
+                .find(|(_, name, _)| name == component)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("{prefix} not found in tree"),
+                    )
+                })?;
+            if !path.is_empty() {
+                path.push('/');
+            }
+            path.push_str(component);
+
+            match entry.0 & 0o170000 {
+                0o040000 => tree = entry.2,
+                0o100000 => return Ok(vec![(path, entry.2)]),
+                _ => return Ok(Vec::new()),
+            }
+        }
+
+        let mut blobs = Vec::new();
+        self.walk_tree(&tree, &path, &mut blobs)?;
+        Ok(blobs)
+    }
+
+    fn walk_tree(&self, tree: &Oid, path: &str, blobs: &mut Vec<(String, Oid)>) -> io::Result<()> {
+        for (mode, name, oid) in self.tree_entries(tree)? {
+            let path = if path.is_empty() {
+                name
+            } else {
+                format!("{path}/{name}")
+            };
+            match mode & 0o170000 {
+                0o040000 => self.walk_tree(&oid, &path, blobs)?,
+                // Symlinks and submodules have no content to search
+                0o100000 => blobs.push((path, oid)),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn tree_entries(&self, tree: &Oid) -> io::Result<Vec<(u32, String, Oid)>> {
+        let (kind, data) = self.read(tree)?;
+        if kind != Kind::Tree {
+            return Err(corrupt("expected a tree object"));
+        }
+
+        let mut entries = Vec::new();
+        let mut rest = &data[..];
+        while !rest.is_empty() {
+            let space = rest.iter().position(|&b| b == b' ');
+            let nul = rest.iter().position(|&b| b == 0);
+            let (space, nul) = match (space, nul) {
+                (Some(space), Some(nul)) if space < nul && nul + 21 <= rest.len() => (space, nul),
+                _ => return Err(corrupt("malformed tree object")),
+            };
+            let mode = std::str::from_utf8(&rest[..space])
+                .ok()
+                .and_then(|mode| u32::from_str_radix(mode, 8).ok())
+                .ok_or_else(|| corrupt("malformed tree object"))?;
+            let name = String::from_utf8_lossy(&rest[space + 1..nul]).into_owned();
+            let mut oid = [0; 20];
+            oid.copy_from_slice(&rest[nul + 1..nul + 21]);
+            entries.push((mode, name, oid));
+            rest = &rest[nul + 21..];
+        }
+        Ok(entries)
+    }
+
+    /// Lists every regular file under `prefix` staged in the index.
+    pub fn index_blobs(&self, prefix: &str) -> io::Result<Vec<(String, Oid)>> {
+        let data = fs::read(self.git_dir.join("index"))?;
+        if data.len() < 12 || &data[..4] != b"DIRC" {
+            return Err(corrupt("malformed index"));
+        }
+        let version = read_u32(&data, 4);
+        if !(2..=4).contains(&version) {
+            return Err(corrupt("unsupported index version"));
+        }
+
+        let mut blobs = Vec::new();
+        let mut pos = 12;
+        let mut previous: Vec<u8> = Vec::new();
+        for _ in 0..read_u32(&data, 8) {
+            let start = pos;
+            if pos + 62 > data.len() {
+                return Err(corrupt("truncated index"));
+            }
+            let mode = read_u32(&data, pos + 24);
+            let mut oid = [0; 20];
+            oid.copy_from_slice(&data[pos + 40..pos + 60]);
+            let flags = u16::from_be_bytes([data[pos + 60], data[pos + 61]]);
+            pos += 62;
+            if version >= 3 && flags & 0x4000 != 0 {
+                pos += 2;
+            }
+
+            let name = if version == 4 {
+                let (strip, len) = read_offset(&data[pos..])?;
+                pos += len;
+                let nul = find_nul(&data[pos..])?;
+                previous.truncate(previous.len().saturating_sub(strip as usize));
+                previous.extend_from_slice(&data[pos..pos + nul]);
+                pos += nul + 1;
+                previous.clone()
+            } else {
+                let nul = find_nul(&data[pos..])?;
+                let name = data[pos..pos + nul].to_vec();
+                // Entries are NUL-padded to a multiple of eight bytes
+                pos = start + (pos + nul - start + 8) / 8 * 8;
+                name
+            };
+
+            let stage = (flags >> 12) & 0x3;
+            if stage == 0 && mode & 0o170000 == 0o100000 {
+                let name = String::from_utf8_lossy(&name).into_owned();
+                if prefix.is_empty() || name == prefix || name.starts_with(&format!("{prefix}/")) {
+                    blobs.push((name, oid));
+                }
+            }
+        }
+        Ok(blobs)
+    }
+
+    pub fn read_blob(&self, oid: &Oid) -> io::Result<Vec<u8>> {
+        match self.read(oid)? {
+            (Kind::Blob, data) => Ok(data),
+            _ => Err(corrupt("expected a blob object")),
+        }
+    }
+
+    fn read(&self, oid: &Oid) -> io::Result<(Kind, Vec<u8>)> {
+        let hex = to_hex(oid);
+        let loose = self
+            .common_dir
+            .join("objects")
+            .join(&hex[..2])
+            .join(&hex[2..]);
+        if let Ok(file) = File::open(&loose) {
+            let mut data = Vec::new();
+            ZlibDecoder::new(BufReader::new(file)).read_to_end(&mut data)?;
+            let nul = find_nul(&data)?;
+            let kind = data[..nul]
+                .split(|&b| b == b' ')
+                .next()
+                .map(Kind::from_name)
+                .unwrap_or_else(|| Err(corrupt("malformed loose object")))?;
+            return Ok((kind, data.split_off(nul + 1)));
+        }
+
+        for pack in &self.packs {
+            if let Some(n) = pack.range(oid).next() {
+                return self.read_packed(pack, pack.offset(n));
+            }
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("object {hex} not found"),
+        ))
+    }
+
+    fn read_packed(&self, pack: &Pack, offset: u64) -> io::Result<(Kind, Vec<u8>)> {
+        let mut file = File::open(&pack.path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut reader = BufReader::new(file);
+
+        let mut byte = read_byte(&mut reader)?;
+        let kind = (byte >> 4) & 0x7;
+        let mut size = (byte & 0x0f) as u64;
+        let mut shift = 4;
+        while byte & 0x80 != 0 {
+            byte = read_byte(&mut reader)?;
+            size |= ((byte & 0x7f) as u64) << shift;
+            shift += 7;
+        }
+
+        let base = match kind {
+            1 => return Ok((Kind::Commit, inflate(&mut reader, size)?)),
+            2 => return Ok((Kind::Tree, inflate(&mut reader, size)?)),
+            3 => return Ok((Kind::Blob, inflate(&mut reader, size)?)),
+            4 => return Ok((Kind::Tag, inflate(&mut reader, size)?)),
+            6 => {
+                let mut byte = read_byte(&mut reader)?;
+                let mut distance = (byte & 0x7f) as u64;
+                while byte & 0x80 != 0 {
+                    byte = read_byte(&mut reader)?;
+                    distance = ((distance + 1) << 7) | (byte & 0x7f) as u64;
+                }
+                let base_offset = offset
+                    .checked_sub(distance)
+                    .ok_or_else(|| corrupt("malformed delta object"))?;
+                self.read_packed(pack, base_offset)?
+            }
+            7 => {
+                let mut base = [0; 20];
+                reader.read_exact(&mut base)?;
+                self.read(&base)?
+            }
+            _ => return Err(corrupt("unknown packed object type")),
+        };
+
+        let delta = inflate(&mut reader, size)?;
+        Ok((base.0, apply_delta(&base.1, &delta)?))
+    }
+}
+
+/// Canonical form of `path`, its part that no longer exists taken as
+/// written on top of the longest prefix that does.
+fn resolve(path: &Path) -> io::Result<PathBuf> {
+    let path = std::path::absolute(path)?;
+    let components: Vec<Component> = path.components().collect();
+    let (mut resolved, rest) = (1..=components.len())
+        .rev()
+        .find_map(|len| {
+            let prefix: PathBuf = components[..len].iter().collect();
+            Some((prefix.canonicalize().ok()?, &components[len..]))
+        })
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such path"))?;
+
+    for component in rest {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(name) => resolved.push(name),
+            _ => {}
+        }
+    }
+    Ok(resolved)
+}
+
+fn apply_delta(base: &[u8], delta: &[u8]) -> io::Result<Vec<u8>> {
+    let mut pos = 0;
+    let varint = |pos: &mut usize| -> io::Result<usize> {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = *delta.get(*pos).ok_or_else(|| corrupt("truncated delta"))?;
+            *pos += 1;
+            value |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+    };
+
+    if varint(&mut pos)? != base.len() {
+        return Err(corrupt("delta base size mismatch"));
+    }
+    let mut result = Vec::with_capacity(varint(&mut pos)?);
+
+    while pos < delta.len() {
+        let op = delta[pos];
+        pos += 1;
+        if op & 0x80 != 0 {
+            let mut fields = [0usize; 7];
+            for (bit, field) in fields.iter_mut().enumerate() {
+                if op & (1 << bit) != 0 {
+                    *field = *delta.get(pos).ok_or_else(|| corrupt("truncated delta"))? as usize;
+                    pos += 1;
+                }
+            }
+            let offset = fields[0] | fields[1] << 8 | fields[2] << 16 | fields[3] << 24;
+            let size = match fields[4] | fields[5] << 8 | fields[6] << 16 {
+                0 => 0x10000,
+                size => size,
+            };
+            let chunk = base
+                .get(offset..offset + size)
+                .ok_or_else(|| corrupt("delta copy out of range"))?;
+            result.extend_from_slice(chunk);
+        } else if op != 0 {
+            let chunk = delta
+                .get(pos..pos + op as usize)
+                .ok_or_else(|| corrupt("truncated delta"))?;
+            result.extend_from_slice(chunk);
+            pos += op as usize;
+        } else {
+            return Err(corrupt("invalid delta opcode"));
+        }
+    }
+
+    Ok(result)
+}
+
+fn inflate(reader: &mut impl BufRead, size: u64) -> io::Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(size as usize);
+    ZlibDecoder::new(reader).read_to_end(&mut data)?;
+    if data.len() as u64 != size {
+        return Err(corrupt("packed object size mismatch"));
+    }
+    Ok(data)
+}
+
+/// Lines of a commit or tag header, up to the blank line before the message.
+fn header_lines(data: &[u8]) -> impl Iterator<Item = &str> {
+    let end = data
+        .windows(2)
+        .position(|w| w == b"\n\n")
+        .unwrap_or(data.len());
+    std::str::from_utf8(&data[..end])
+        .unwrap_or_default()
+        .lines()
+}
+
+/// Variable-length integer used by index v4 path compression.
+fn read_offset(data: &[u8]) -> io::Result<(u64, usize)> {
+    let mut value = 0u64;
+    for (n, &byte) in data.iter().enumerate() {
+        value = if n == 0 {
+            (byte & 0x7f) as u64
+        } else {
+            ((value + 1) << 7) | (byte & 0x7f) as u64
+        };
+        if byte & 0x80 == 0 {
+            return Ok((value, n + 1));
+        }
+    }
+    Err(corrupt("truncated index"))
+}
+
+fn read_byte(reader: &mut impl Read) -> io::Result<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+}
+
+fn find_nul(data: &[u8]) -> io::Result<usize> {
+    data.iter()
+        .position(|&b| b == 0)
+        .ok_or_else(|| corrupt("missing NUL terminator"))
+}
+
+fn parse_hex(hex: &str) -> io::Result<Oid> {
+    let hex = hex.trim();
+    if hex.len() != 40 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(corrupt("malformed object id"));
+    }
+    let mut oid = [0; 20];
+    oid.copy_from_slice(&parse_hex_prefix(hex));
+    Ok(oid)
+}
+
+fn parse_hex_prefix(hex: &str) -> Vec<u8> {
+    (0..hex.len() / 2)
+        .map(|n| u8::from_str_radix(&hex[n * 2..n * 2 + 2], 16).unwrap_or(0))
+        .collect()
+}
+
+fn to_hex(oid: &[u8]) -> String {
+    oid.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn corrupt(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn bad_rev(rev: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("unknown revision `{rev}`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// Variable-length integer of ofs-delta distances and index v4 paths,
+    /// as `read_offset` reads it.
+    fn offset_varint(mut value: u64) -> Vec<u8> {
+        let mut bytes = vec![(value & 0x7f) as u8];
+        while value >> 7 != 0 {
+            value = (value >> 7) - 1;
+            bytes.insert(0, 0x80 | (value & 0x7f) as u8);
+        }
+        bytes
+    }
+
+    /// Size varint at the start of a delta.
+    fn size_varint(mut value: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        while value >= 0x80 {
+            bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        bytes.push(value as u8);
+        bytes
+    }
+
+    /// Type and size header of a packed object.
+    fn pack_header(kind: u8, size: usize) -> Vec<u8> {
+        let mut bytes = vec![kind << 4 | (size & 0x0f) as u8];
+        let mut size = size >> 4;
+        while size != 0 {
+            *bytes.last_mut().unwrap() |= 0x80;
+            bytes.push((size & 0x7f) as u8);
+            size >>= 7;
+        }
+        bytes
+    }
+
+    fn copy(offset: usize, size: usize) -> Vec<u8> {
+        let mut op = vec![0x80];
+        for (bit, byte) in offset.to_le_bytes()[..4].iter().enumerate() {
+            if *byte != 0 {
+                op[0] |= 1 << bit;
+                op.push(*byte);
+            }
+        }
+        for (bit, byte) in size.to_le_bytes()[..3].iter().enumerate() {
+            if *byte != 0 {
+                op[0] |= 1 << (bit + 4);
+                op.push(*byte);
+            }
+        }
+        op
+    }
+
+    fn insert(data: &[u8]) -> Vec<u8> {
+        let mut op = vec![data.len() as u8];
+        op.extend_from_slice(data);
+        op
+    }
+
+    fn encode_delta(base: usize, result: usize, ops: &[Vec<u8>]) -> Vec<u8> {
+        let mut delta = size_varint(base);
+        delta.extend(size_varint(result));
+        delta.extend(ops.concat());
+        delta
+    }
+
+    /// Repository written object by object. Object ids are made up, as
+    /// nothing checks them against the contents.
+    struct Fixture {
+        dir: PathBuf,
+        next: u8,
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Fixture {
+            let dir = std::env::temp_dir().join(format!("qgrep-git-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(dir.join(".git/objects/pack")).unwrap();
+            fs::create_dir_all(dir.join(".git/refs/heads")).unwrap();
+            Fixture { dir, next: 0 }
+        }
+
+        fn git_dir(&self) -> PathBuf {
+            self.dir.join(".git")
+        }
+
+        fn repo(&self) -> Repository {
+            Repository::discover(&self.dir).unwrap()
+        }
+
+        fn oid(&mut self) -> Oid {
+            self.next += 1;
+            [self.next; 20]
+        }
+
+        fn loose(&self, oid: Oid, kind: &str, data: &[u8]) {
+            let hex = to_hex(&oid);
+            let dir = self.git_dir().join("objects").join(&hex[..2]);
+            fs::create_dir_all(&dir).unwrap();
+            let mut object = format!("{kind} {}\0", data.len()).into_bytes();
+            object.extend_from_slice(data);
+            fs::write(dir.join(&hex[2..]), zlib(&object)).unwrap();
+        }
+
+        fn object(&mut self, kind: &str, data: &[u8]) -> Oid {
+            let oid = self.oid();
+            self.loose(oid, kind, data);
+            oid
+        }
+
+        fn tree(&mut self, entries: &[(&str, &str, Oid)]) -> Oid {
+            let mut data = Vec::new();
+            for (mode, name, oid) in entries {
+                data.extend(format!("{mode} {name}\0").bytes());
+                data.extend_from_slice(oid);
+            }
+            self.object("tree", &data)
+        }
+
+        fn commit(&mut self, tree: Oid, parents: &[Oid]) -> Oid {
+            let mut data = format!("tree {}\n", to_hex(&tree));
+            for parent in parents {
+                data.push_str(&format!("parent {}\n", to_hex(parent)));
+            }
+            // The message must not be taken for headers
+            data.push_str("author A <a@example.com> 0 +0000\n\nparent 00\n");
+            self.object("commit", data.as_bytes())
+        }
+
+        fn write(&self, path: &str, data: impl AsRef<[u8]>) {
+            let path = self.git_dir().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, data).unwrap();
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// Index file of the given version, entries being path, mode, id and
+    /// stage.
+    fn index(version: u32, entries: &[(&str, u32, Oid, u16)]) -> Vec<u8> {
+        let mut data = b"DIRC".to_vec();
+        data.extend(version.to_be_bytes());
+        data.extend((entries.len() as u32).to_be_bytes());
+        let mut previous: &[u8] = &[];
+        for &(path, mode, oid, stage) in entries {
+            let start = data.len();
+            data.extend([0; 24]);
+            data.extend(mode.to_be_bytes());
+            data.extend([0; 12]);
+            data.extend(oid);
+            let mut flags = stage << 12 | path.len().min(0xfff) as u16;
+            if version == 3 {
+                flags |= 0x4000;
+            }
+            data.extend(flags.to_be_bytes());
+            if version == 3 {
+                data.extend([0; 2]);
+            }
+
+            let path = path.as_bytes();
+            if version == 4 {
+                let shared = previous
+                    .iter()
+                    .zip(path)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                data.extend(offset_varint((previous.len() - shared) as u64));
+                data.extend_from_slice(&path[shared..]);
+                data.push(0);
+            } else {
+                data.extend_from_slice(path);
+                let len = data.len() - start;
+                data.resize(start + (len + 8) / 8 * 8, 0);
+            }
+            previous = path;
+        }
+        data
+    }
+
+    #[test]
+    fn delta_copies_and_inserts() {
+        let base = b"hello world";
+        let delta = encode_delta(
+            base.len(),
+            18,
+            &[copy(0, 6), insert(b"there "), copy(6, 5), insert(b"!")],
+        );
+        assert_eq!(apply_delta(base, &delta).unwrap(), b"hello there world!");
+
+        // A copy of size zero means 64 KiB
+        let base = vec![7; 0x10000];
+        let delta = encode_delta(base.len(), base.len(), &[vec![0x80]]);
+        assert_eq!(apply_delta(&base, &delta).unwrap(), base);
+    }
+
+    #[test]
+    fn malformed_deltas_are_rejected() {
+        let base = b"hello world";
+        let error = |delta: &[u8]| apply_delta(base, delta).unwrap_err().to_string();
+        assert_eq!(
+            error(&encode_delta(10, 5, &[copy(0, 5)])),
+            "delta base size mismatch"
+        );
+        assert_eq!(
+            error(&encode_delta(11, 5, &[copy(8, 5)])),
+            "delta copy out of range"
+        );
+        assert_eq!(
+            error(&encode_delta(11, 5, &[vec![0]])),
+            "invalid delta opcode"
+        );
+        assert_eq!(
+            error(&encode_delta(11, 5, &[vec![5, b'a']])),
+            "truncated delta"
+        );
+        assert_eq!(
+            error(&encode_delta(11, 5, &[vec![0x91, 0]])),
+            "truncated delta"
+        );
+        assert_eq!(error(&[0x80]), "truncated delta");
+    }
+
+    #[test]
+    fn index_versions() {
+        let blob = [0xab; 20];
+        let entries = [
+            ("README.md", 0o100644, blob, 0),
+            ("src/lib.rs", 0o100644, blob, 0),
+            ("src/link", 0o120000, blob, 0),
+            ("src/main.rs", 0o100755, blob, 0),
+            ("src/main.rs", 0o100644, blob, 2),
+            ("srcs/other.rs", 0o100644, blob, 0),
+        ];
+        for version in 2..=4 {
+            let fixture = Fixture::new(&format!("index-v{version}"));
+            fixture.write("index", index(version, &entries));
+            let repo = fixture.repo();
+            let names = |prefix| -> Vec<String> {
+                let blobs = repo.index_blobs(prefix).unwrap();
+                blobs.into_iter().map(|(name, _)| name).collect()
+            };
+            assert_eq!(
+                names(""),
+                ["README.md", "src/lib.rs", "src/main.rs", "srcs/other.rs"],
+                "version {version}"
+            );
+            assert_eq!(names("src"), ["src/lib.rs", "src/main.rs"]);
+            assert_eq!(names("README.md"), ["README.md"]);
+        }
+    }
+
+    #[test]
+    fn malformed_indexes_are_rejected() {
+        let fixture = Fixture::new("bad-index");
+        let error = |data: &[u8]| {
+            fixture.write("index", data);
+            fixture.repo().index_blobs("").unwrap_err().to_string()
+        };
+        assert_eq!(error(b"DIRX\0\0\0\x02\0\0\0\0"), "malformed index");
+        assert_eq!(error(&index(5, &[])), "unsupported index version");
+        let data = index(2, &[("file", 0o100644, [1; 20], 0)]);
+        assert_eq!(error(&data[..40]), "truncated index");
+    }
+
+    #[test]
+    fn trees_list_regular_files() {
+        let mut fixture = Fixture::new("tree");
+        let (a, b, c) = (fixture.oid(), fixture.oid(), fixture.oid());
+        let inner = fixture.tree(&[("100644", "c.rs", c)]);
+        let src = fixture.tree(&[
+            ("100644", "b.rs", b),
+            ("40000", "inner", inner),
+            ("120000", "link", a),
+            ("160000", "submodule", a),
+        ]);
+        let root = fixture.tree(&[("100644", "a.txt", a), ("40000", "src", src)]);
+        let repo = fixture.repo();
+
+        assert_eq!(
+            repo.tree_blobs(&root, "").unwrap(),
+            [
+                ("a.txt".to_string(), a),
+                ("src/b.rs".to_string(), b),
+                ("src/inner/c.rs".to_string(), c)
+            ]
+        );
+        assert_eq!(
+            repo.tree_blobs(&root, "src/inner").unwrap(),
+            [("src/inner/c.rs".to_string(), c)]
+        );
+        assert_eq!(
+            repo.tree_blobs(&root, "src/b.rs").unwrap(),
+            [("src/b.rs".to_string(), b)]
+        );
+        assert!(repo.tree_blobs(&root, "src/link").unwrap().is_empty());
+        assert_eq!(
+            repo.tree_blobs(&root, "missing").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn revisions_resolve_to_trees() {
+        let mut fixture = Fixture::new("revs");
+        let trees: Vec<Oid> = (0..4).map(|_| fixture.tree(&[])).collect();
+        let first = fixture.commit(trees[0], &[]);
+        let side = fixture.commit(trees[1], &[first]);
+        let second = fixture.commit(trees[2], &[first]);
+        let merge = fixture.commit(trees[3], &[second, side]);
+        let tag = fixture.object(
+            "tag",
+            format!("object {}\ntype commit\ntag v1\n\nFirst\n", to_hex(&first)).as_bytes(),
+        );
+        fixture.write("HEAD", "ref: refs/heads/main\n");
+        fixture.write("refs/heads/main", format!("{}\n", to_hex(&merge)));
+        fixture.write(
+            "packed-refs",
+            format!(
+                "# pack-refs with: peeled\n{} refs/tags/v1\n^{}\n{} refs/remotes/origin/side\n",
+                to_hex(&tag),
+                to_hex(&first),
+                to_hex(&side)
+            ),
+        );
+        let repo = fixture.repo();
+        let tree = |rev: &str| repo.resolve_tree(rev).unwrap();
+
+        assert_eq!(tree("HEAD"), trees[3]);
+        assert_eq!(tree("@"), trees[3]);
+        assert_eq!(tree("main"), trees[3]);
+        assert_eq!(tree("refs/heads/main"), trees[3]);
+        assert_eq!(tree("main~"), trees[2]);
+        assert_eq!(tree("main~1"), trees[2]);
+        assert_eq!(tree("HEAD^"), trees[2]);
+        assert_eq!(tree("HEAD^2"), trees[1]);
+        assert_eq!(tree("HEAD~2"), trees[0]);
+        assert_eq!(tree("HEAD^2~1"), trees[0]);
+        assert_eq!(tree("HEAD^0"), trees[3]);
+        assert_eq!(tree("v1"), trees[0]);
+        assert_eq!(tree("origin/side"), trees[1]);
+        assert_eq!(tree(&to_hex(&second)), trees[2]);
+        assert_eq!(tree(&to_hex(&second)[..7]), trees[2]);
+        assert_eq!(tree(&to_hex(&trees[1])[..4]), trees[1]);
+
+        for rev in ["nope", "HEAD~3", "HEAD^3", "HEAD~x", "abc"] {
+            let err = repo.resolve_tree(rev).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("unknown revision `{rev}`"),
+                "{rev}"
+            );
+        }
+    }
+
+    #[test]
+    fn deleted_paths_are_still_located() {
+        let fixture = Fixture::new("deleted");
+        fs::create_dir_all(fixture.dir.join("src")).unwrap();
+        let gone = fixture.dir.join("src/gone/deeper/../file.rs");
+        let repo = Repository::discover(&gone).unwrap();
+        assert_eq!(repo.relative_path(&gone).unwrap(), "src/gone/file.rs");
+        assert_eq!(repo.relative_path(&fixture.dir).unwrap(), "");
+        assert!(repo
+            .relative_path(&fixture.dir.join("../elsewhere"))
+            .is_err());
+    }
+
+    #[test]
+    fn short_ids_must_be_unambiguous() {
+        let fixture = Fixture::new("ambiguous");
+        let mut oid = [0xab; 20];
+        fixture.loose(oid, "blob", b"one");
+        oid[19] = 0xcd;
+        fixture.loose(oid, "blob", b"two");
+        let repo = fixture.repo();
+        assert_eq!(
+            repo.resolve_tree("abab").unwrap_err().to_string(),
+            "short object id abab is ambiguous"
+        );
+        assert_eq!(
+            repo.resolve_tree(&to_hex(&oid)[..39])
+                .unwrap_err()
+                .to_string(),
+            format!("{} is not a Tree", to_hex(&oid))
+        );
+    }
+
+    #[test]
+    fn packed_objects_and_deltas() {
+        let mut fixture = Fixture::new("pack");
+        let base = b"hello world".to_vec();
+        let target = b"hello there world!".to_vec();
+        let ops = [copy(0, 6), insert(b"there "), copy(6, 5), insert(b"!")];
+        let delta = encode_delta(base.len(), target.len(), &ops);
+
+        // A blob, then a delta against it by offset
+        let mut pack = b"PACK\0\0\0\x02\0\0\0\x02".to_vec();
+        let base_offset = pack.len();
+        pack.extend(pack_header(3, base.len()));
+        pack.extend(zlib(&base));
+        let delta_offset = pack.len();
+        pack.extend(pack_header(6, delta.len()));
+        pack.extend(offset_varint((delta_offset - base_offset) as u64));
+        pack.extend(zlib(&delta));
+
+        let mut objects = [(fixture.oid(), base_offset), (fixture.oid(), delta_offset)];
+        objects[0].0[0] = 0x10;
+        objects[1].0[0] = 0xf0;
+        let mut idx = vec![0xff, b't', b'O', b'c', 0, 0, 0, 2];
+        for byte in 0..256 {
+            let count = objects
+                .iter()
+                .filter(|(oid, _)| oid[0] as usize <= byte)
+                .count();
+            idx.extend((count as u32).to_be_bytes());
+        }
+        for (oid, _) in &objects {
+            idx.extend(oid);
+        }
+        idx.extend([0; 8]);
+        for (_, offset) in &objects {
+            idx.extend((*offset as u32).to_be_bytes());
+        }
+        fixture.write("objects/pack/pack-new.pack", &pack);
+        fixture.write("objects/pack/pack-new.idx", &idx);
+        // Version 1 indexes are skipped, the rest of the packs still usable
+        fixture.write("objects/pack/pack-old.idx", [0; 256 * 4]);
+
+        let repo = fixture.repo();
+        assert_eq!(repo.packs.len(), 1);
+        assert_eq!(repo.read_blob(&objects[0].0).unwrap(), base);
+        assert_eq!(repo.read_blob(&objects[1].0).unwrap(), target);
+        assert_eq!(
+            repo.expand_oid(&to_hex(&objects[1].0)[..5]).unwrap(),
+            objects[1].0
+        );
+        assert_eq!(
+            repo.read(&[0x42; 20]).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+}
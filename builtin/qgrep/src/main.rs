@@ -1,14 +1,58 @@
+mod git;
+mod syntax;
+
+use common::glob::glob_to_regex;
+use common::lines;
 use regex::Regex;
-use std::io::BufRead;
+use std::collections::hash_map::RandomState;
+use std::fmt::Write as _;
+use std::fs::{DirBuilder, File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::sync::{mpsc, Arc};
 use std::thread;
+use syntax::{Language, Region};
 use text_colorizer::*;
 
+/// Amount of leading bytes inspected when deciding whether a file is binary.
+const BINARY_PROBE_LEN: usize = 8192;
+
+#[derive(Clone, Copy)]
+struct Output {
+    files_only: bool,
+    null: bool,
+    with_filename: bool,
+    heading: bool,
+}
+
+impl Output {
+    fn terminator(&self) -> char {
+        if self.null {
+            '\0'
+        } else {
+            '\n'
+        }
+    }
+}
+
+enum GitSource {
+    Rev(String),
+    Index,
+}
+
 struct Config {
     pattern: String,
     path: String,
     recursive: bool,
     ignore_case: bool,
+    pre: Option<String>,
+    pre_globs: Vec<String>,
+    output: Output,
+    git: Option<GitSource>,
+    syntax: Option<Region>,
 }
 
 impl Config {
@@ -19,13 +63,42 @@ impl Config {
 
         let mut recursive = false;
         let mut ignore_case = false;
+        let mut pre = None;
+        let mut pre_globs = Vec::new();
+        let mut output = Output {
+            files_only: false,
+            null: false,
+            with_filename: true,
+            heading: true,
+        };
+        let mut git = None;
+        let mut syntax = None;
         let mut pattern = String::new();
         let mut path = String::new();
 
-        for arg in args.iter().skip(1) {
+        let mut args = args.iter().skip(1);
+        while let Some(arg) = args.next() {
             match arg.as_str() {
                 "-r" => recursive = true,
                 "-i" => ignore_case = true,
+                "-l" | "--files-with-matches" => output.files_only = true,
+                "-0" | "--null" => output.null = true,
+                "-H" | "--with-filename" => output.with_filename = true,
+                "--no-filename" => output.with_filename = false,
+                "--no-heading" => output.heading = false,
+                "--pre" => pre = Some(args.next().ok_or("--pre requires a command")?.clone()),
+                "--pre-glob" => {
+                    pre_globs.push(args.next().ok_or("--pre-glob requires a glob")?.clone())
+                }
+                "--git-rev" => {
+                    let rev = args.next().ok_or("--git-rev requires a revision")?;
+                    git = Some(GitSource::Rev(rev.clone()));
+                }
+                "--cached" => git = Some(GitSource::Index),
+                _ if arg.starts_with("--syntax=") => {
+                    let region = Region::from_name(&arg["--syntax=".len()..]);
+                    syntax = Some(region.ok_or("--syntax expects code, comments or strings")?);
+                }
                 _ if pattern.is_empty() => pattern.clone_from(arg),
                 _ => path.clone_from(arg),
             }
@@ -35,54 +108,241 @@ impl Config {
             return Err("Pattern or path missing");
         }
 
+        if pre.is_none() && !pre_globs.is_empty() {
+            return Err("--pre-glob requires --pre");
+        }
+
         Ok(Config {
             pattern,
             path,
             recursive,
             ignore_case,
+            pre,
+            pre_globs,
+            output,
+            git,
+            syntax,
         })
     }
 }
 
-fn search_in_file(regex: &Regex, path: &str) -> Result<(), std::io::Error> {
-    let file = std::fs::File::open(path)?;
-    let reader = std::io::BufReader::new(file);
-    let mut has_match = false;
+struct Searcher {
+    regex: Regex,
+    /// Same pattern, matched against raw lines when filtering on regions
+    bytes_regex: regex::bytes::Regex,
+    recursive: bool,
+    pre: Option<String>,
+    pre_globs: Vec<Glob>,
+    output: Output,
+    syntax: Option<Region>,
+}
 
-    for (line_number, line) in reader.lines().enumerate() {
-        let line = line?;
-        if regex.is_match(&line) {
-            if !has_match {
-                println!("\n{}:", path.bold().blue());
-                has_match = true;
-            }
-            println!("{}: {}", line_number + 1, line);
+impl Searcher {
+    /// `regions` covers the raw bytes of `line`, before any lossy decoding.
+    fn is_match(&self, line: &str, regions: Option<(&[u8], &[Region])>) -> bool {
+        match (self.syntax, regions) {
+            // Match the raw bytes, the offsets in the decoded line are off
+            // after invalid UTF-8
+            (Some(target), Some((raw, regions))) => self.bytes_regex.find_iter(raw).any(|m| {
+                regions
+                    .get(m.start()..m.end().max(m.start() + 1))
+                    .is_some_and(|regions| regions.iter().all(|&region| region == target))
+            }),
+            _ => self.regex.is_match(line),
+        }
+    }
+
+    fn preprocessor_for(&self, path: &str) -> Option<&str> {
+        let pre = self.pre.as_deref()?;
+        if self.pre_globs.is_empty() || self.pre_globs.iter().any(|glob| glob.is_match(path)) {
+            Some(pre)
+        } else {
+            None
+        }
+    }
+}
+
+/// Shell-style glob (`*`, `**`, `?`, `[...]`, `{a,b}`) compiled to a regex.
+///
+/// Globs without a `/` are matched against the file name only, otherwise
+/// against the whole path, less any leading `./`.
+struct Glob {
+    regex: Regex,
+    basename_only: bool,
+}
+
+impl Glob {
+    fn new(glob: &str) -> Result<Glob, regex::Error> {
+        Ok(Glob {
+            regex: Regex::new(&glob_to_regex(glob))?,
+            basename_only: !glob.contains('/'),
+        })
+    }
+
+    fn is_match(&self, path: &str) -> bool {
+        if self.basename_only {
+            let name = Path::new(path)
+                .file_name()
+                .map(|name| name.to_string_lossy())
+                .unwrap_or_default();
+            self.regex.is_match(&name)
+        } else {
+            // `docs/*.txt` should match whether the search started at `docs`
+            // or at `.`
+            self.regex.is_match(path.trim_start_matches("./"))
+        }
+    }
+}
+
+/// Output of the preprocessor `pre` run on `file`, `None` if it failed.
+/// `path` is the name the file is reported under.
+fn preprocess(pre: &str, file: &Path, path: &str) -> Result<Option<Vec<u8>>, std::io::Error> {
+    let output = Command::new(pre)
+        .arg(file)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        eprintln!(
+            "{}: preprocessor `{}` failed on {}: {}",
+            "Error".red().bold(),
+            pre,
+            path,
+            output.status
+        );
+        return Ok(None);
+    }
+    Ok(Some(output.stdout))
+}
+
+fn search_in_file(searcher: &Searcher, path: &str) -> Result<(), std::io::Error> {
+    match searcher.preprocessor_for(path) {
+        Some(pre) => match preprocess(pre, Path::new(path), path)? {
+            Some(output) => search_in_reader(searcher, path, &output[..]),
+            None => Ok(()),
+        },
+        None => {
+            let file = File::open(path)?;
+            search_in_reader(searcher, path, BufReader::new(file))
         }
     }
+}
+
+fn search_in_reader(
+    searcher: &Searcher,
+    path: &str,
+    mut reader: impl BufRead,
+) -> Result<(), std::io::Error> {
+    if searcher.syntax.is_none() {
+        return search_lines(searcher, path, reader, None);
+    }
+
+    // Files in languages we can't lex have no regions to filter on
+    let Some(language) = Language::from_path(path) else {
+        return Ok(());
+    };
+    let mut source = Vec::new();
+    reader.read_to_end(&mut source)?;
+    let regions = syntax::region_map(&source, language);
+    search_lines(searcher, path, &source[..], Some((&source, &regions)))
+}
+
+/// Searches the lines of `reader`. With `regions`, the reader must be over
+/// the source given along with them.
+fn search_lines(
+    searcher: &Searcher,
+    path: &str,
+    mut reader: impl BufRead,
+    regions: Option<(&[u8], &[Region])>,
+) -> Result<(), std::io::Error> {
+    let probe = reader.fill_buf()?;
+    let is_binary = probe[..probe.len().min(BINARY_PROBE_LEN)].contains(&0);
+    let output = searcher.output;
+    let mut out = String::new();
+
+    // Without regions, listing files only needs the first match
+    if output.files_only && regions.is_none() {
+        if lines::any_line_matches(&searcher.regex, reader)? {
+            let _ = write!(out, "{}{}", path.bold().blue(), output.terminator());
+        }
+    } else {
+        let mut line_number = 0;
+        let mut offset = 0;
+        lines::for_each_line(reader, |line, len| {
+            line_number += 1;
+            let line_regions = regions.map(|(source, regions)| {
+                let raw = &source[offset..offset + len];
+                let end = raw
+                    .iter()
+                    .rposition(|byte| !matches!(byte, b'\n' | b'\r'))
+                    .map_or(0, |last| last + 1);
+                (&raw[..end], &regions[offset..offset + len])
+            });
+            offset += len;
+
+            if !searcher.is_match(line, line_regions) {
+                return true;
+            }
+
+            if output.files_only {
+                let _ = write!(out, "{}{}", path.bold().blue(), output.terminator());
+                return false;
+            }
+            if is_binary {
+                let _ = writeln!(out, "Binary file {} matches", path.bold().blue());
+                return false;
+            }
+
+            if !output.with_filename {
+                let _ = writeln!(out, "{}: {}", line_number, line);
+            } else if !output.heading {
+                let separator = if output.null { '\0' } else { ':' };
+                let _ = writeln!(
+                    out,
+                    "{}{}{}:{}",
+                    path.bold().blue(),
+                    separator,
+                    line_number,
+                    line
+                );
+            } else {
+                if out.is_empty() {
+                    let separator = if output.null { '\0' } else { ':' };
+                    let _ = writeln!(out, "\n{}{}", path.bold().blue(), separator);
+                }
+                let _ = writeln!(out, "{}: {}", line_number, line);
+            }
+            true
+        })?;
+    }
+
+    // Print the whole file at once so output of concurrent searches doesn't interleave
+    if !out.is_empty() {
+        std::io::stdout().lock().write_all(out.as_bytes())?;
+    }
 
     Ok(())
 }
 
-fn search_in_path(regex: &Regex, path: &str, recursive: bool) -> Result<(), std::io::Error> {
+fn search_in_path(searcher: &Arc<Searcher>, path: &str) -> Result<(), std::io::Error> {
     let metadata = std::fs::metadata(path)?;
     if metadata.is_file() {
-        search_in_file(regex, path)?;
+        search_in_file(searcher, path)?;
     } else if metadata.is_dir() {
         let (tx, rx) = mpsc::channel();
         let path = path.to_string();
-        let regex = Arc::new(regex.clone());
 
         for entry in std::fs::read_dir(&path)? {
             let entry = entry?;
             let path = entry.path();
             let tx = tx.clone();
-            let regex = Arc::clone(&regex);
+            let searcher = Arc::clone(searcher);
 
             thread::spawn(move || {
                 if path.is_file() {
-                    let _ = search_in_file(&regex, path.to_str().unwrap());
-                } else if path.is_dir() && recursive {
-                    let _ = search_in_path(&regex, path.to_str().unwrap(), recursive);
+                    let _ = search_in_file(&searcher, path.to_str().unwrap());
+                } else if path.is_dir() && searcher.recursive {
+                    let _ = search_in_path(&searcher, path.to_str().unwrap());
                 }
                 tx.send(()).unwrap();
             });
@@ -98,12 +358,102 @@ fn search_in_path(regex: &Regex, path: &str, recursive: bool) -> Result<(), std:
     Ok(())
 }
 
+fn search_in_git(
+    searcher: &Searcher,
+    path: &str,
+    source: &GitSource,
+) -> Result<(), std::io::Error> {
+    let repo = git::Repository::discover(Path::new(path))?;
+    let prefix = repo.relative_path(Path::new(path))?;
+    // Staged files are reported by path alone, like `git grep --cached`
+    let (label, blobs) = match source {
+        GitSource::Rev(rev) => (
+            Some(rev.as_str()),
+            repo.tree_blobs(&repo.resolve_tree(rev)?, &prefix)?,
+        ),
+        GitSource::Index => (None, repo.index_blobs(&prefix)?),
+    };
+
+    let mut scratch = None;
+    for (blob_path, oid) in blobs {
+        let relative = blob_path[prefix.len()..].trim_start_matches('/');
+        if !searcher.recursive && relative.contains('/') {
+            continue;
+        }
+        let data = repo.read_blob(&oid)?;
+        let path = match label {
+            Some(label) => format!("{label}:{blob_path}"),
+            None => blob_path.clone(),
+        };
+        let Some(pre) = searcher.preprocessor_for(&blob_path) else {
+            search_in_reader(searcher, &path, &data[..])?;
+            continue;
+        };
+
+        // Preprocessors take a path, so blobs are written out under their
+        // own name for those that go by the extension
+        if scratch.is_none() {
+            scratch = Some(ScratchDir::new()?);
+        }
+        let scratch = scratch.as_ref().unwrap();
+        let name = blob_path.rsplit('/').next().unwrap_or_default();
+        let file = scratch.write(name, &data)?;
+        let output = preprocess(pre, &file, &path);
+        std::fs::remove_file(&file)?;
+        if let Some(output) = output? {
+            search_in_reader(searcher, &path, &output[..])?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Private temporary directory, removed along with its contents on drop.
+struct ScratchDir {
+    path: PathBuf,
+}
+
+impl ScratchDir {
+    /// Creates a directory only we can access under a random name, failing
+    /// rather than reusing one that exists, so nobody else can plant files
+    /// or symlinks in it.
+    fn new() -> Result<ScratchDir, std::io::Error> {
+        let random = RandomState::new().build_hasher().finish();
+        let path =
+            std::env::temp_dir().join(format!("qgrep-{}-{:016x}", std::process::id(), random));
+        DirBuilder::new().mode(0o700).create(&path)?;
+        Ok(ScratchDir { path })
+    }
+
+    /// Writes `data` to a new file called `name`, returning its path.
+    fn write(&self, name: &str, data: &[u8]) -> Result<PathBuf, std::io::Error> {
+        // Names from the repository can't be trusted to stay inside
+        let name = match name {
+            "" | "." | ".." => "blob",
+            name => name,
+        };
+        let path = self.path.join(name);
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?
+            .write_all(data)?;
+        Ok(path)
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
 fn main() -> Result<(), std::io::Error> {
     let args: Vec<String> = std::env::args().collect();
     let config = Config::from_args(&args).unwrap_or_else(|err| {
         eprintln!("{}: {}", "--|Error|--".red().bold(), err);
         eprintln!(
-            "{}\n\t {} <pattern> <path> [-r] [-i]",
+            "{}\n\t {} <pattern> <path> [-r] [-i] [-l] [-0] [-H] [--no-filename] [--no-heading] [--pre COMMAND] [--pre-glob GLOB]... [--git-rev REV | --cached] [--syntax=code|comments|strings]",
             "Usage:".bold().blue(),
             args[0]
         );
@@ -116,17 +466,97 @@ fn main() -> Result<(), std::io::Error> {
         config.pattern
     };
 
-    let regex = match Regex::new(&pattern) {
-        Ok(re) => re,
+    let regexes =
+        Regex::new(&pattern).and_then(|regex| Ok((regex, regex::bytes::Regex::new(&pattern)?)));
+    let (regex, bytes_regex) = match regexes {
+        Ok(regexes) => regexes,
         Err(err) => {
             eprintln!("{}: {}", "--|Error|--".red().bold(), err);
             std::process::exit(1);
         }
     };
 
-    if let Err(err) = search_in_path(&regex, &config.path, config.recursive) {
+    let pre_globs = match config
+        .pre_globs
+        .iter()
+        .map(|glob| Glob::new(glob))
+        .collect()
+    {
+        Ok(globs) => globs,
+        Err(err) => {
+            eprintln!("{}: {}", "--|Error|--".red().bold(), err);
+            std::process::exit(1);
+        }
+    };
+
+    let searcher = Arc::new(Searcher {
+        regex,
+        bytes_regex,
+        recursive: config.recursive,
+        pre: config.pre,
+        pre_globs,
+        output: config.output,
+        syntax: config.syntax,
+    });
+
+    let result = match &config.git {
+        Some(source) => search_in_git(&searcher, &config.path, source),
+        None => search_in_path(&searcher, &config.path),
+    };
+    if let Err(err) = result {
         eprintln!("{} Searching in path: {}", "Error".red().bold(), err);
+        std::process::exit(1);
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn searcher(pattern: &str, syntax: Region) -> Searcher {
+        Searcher {
+            regex: Regex::new(pattern).unwrap(),
+            bytes_regex: regex::bytes::Regex::new(pattern).unwrap(),
+            recursive: false,
+            pre: None,
+            pre_globs: Vec::new(),
+            output: Output {
+                files_only: false,
+                null: false,
+                with_filename: false,
+                heading: false,
+            },
+            syntax: Some(syntax),
+        }
+    }
+
+    #[test]
+    fn path_globs_ignore_a_leading_dot_slash() {
+        let glob = Glob::new("docs/*.txt").unwrap();
+        assert!(glob.is_match("docs/a.txt"));
+        assert!(glob.is_match("./docs/a.txt"));
+        assert!(glob.is_match("././docs/a.txt"));
+        assert!(!glob.is_match("other/docs/a.txt"));
+        assert!(!glob.is_match("docs/a.md"));
+
+        let glob = Glob::new("*.txt").unwrap();
+        assert!(glob.is_match("./docs/a.txt"));
+    }
+
+    #[test]
+    fn regions_line_up_after_invalid_utf8() {
+        let raw = b"/*\xff\xff*/ foo // bar";
+        let regions = syntax::region_map(raw, Language::Rust);
+        let line = String::from_utf8_lossy(raw);
+        let is_match =
+            |pattern, region| searcher(pattern, region).is_match(&line, Some((raw, &regions)));
+
+        assert!(is_match("foo", Region::Code));
+        assert!(!is_match("foo", Region::Comment));
+        assert!(is_match("bar", Region::Comment));
+        assert!(!is_match("bar", Region::Code));
+        assert!(is_match(r"\*/", Region::Comment));
+    }
+}
@@ -0,0 +1,381 @@
+//! Small lexers splitting Rust and C sources into code, comment and string
+//! regions. They only understand as much of each language as is needed to
+//! tell those regions apart, not full tokenization.
+
+use std::ops::Range;
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Language {
+    Rust,
+    C,
+}
+
+impl Language {
+    pub fn from_path(path: &str) -> Option<Language> {
+        match Path::new(path).extension()?.to_str()? {
+            "rs" => Some(Language::Rust),
+            "c" | "h" => Some(Language::C),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Region {
+    Code,
+    Comment,
+    String,
+}
+
+impl Region {
+    pub fn from_name(name: &str) -> Option<Region> {
+        match name {
+            "code" => Some(Region::Code),
+            "comments" => Some(Region::Comment),
+            "strings" => Some(Region::String),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Span {
+    pub region: Region,
+    pub range: Range<usize>,
+}
+
+/// Splits `source` into consecutive spans covering every byte.
+pub fn lex(source: &[u8], language: Language) -> Vec<Span> {
+    let mut lexer = Lexer {
+        source,
+        language,
+        pos: 0,
+        code_start: 0,
+        spans: Vec::new(),
+    };
+    lexer.run();
+    lexer.spans
+}
+
+/// Region of every byte of `source`, convenient for checking match ranges.
+pub fn region_map(source: &[u8], language: Language) -> Vec<Region> {
+    let mut map = vec![Region::Code; source.len()];
+    for span in lex(source, language) {
+        map[span.range].fill(span.region);
+    }
+    map
+}
+
+struct Lexer<'a> {
+    source: &'a [u8],
+    language: Language,
+    pos: usize,
+    code_start: usize,
+    spans: Vec<Span>,
+}
+
+impl Lexer<'_> {
+    fn peek(&self, offset: usize) -> Option<u8> {
+        self.source.get(self.pos + offset).copied()
+    }
+
+    fn starts_with(&self, text: &[u8]) -> bool {
+        self.source[self.pos..].starts_with(text)
+    }
+
+    fn push(&mut self, region: Region, start: usize) {
+        if self.code_start < start {
+            self.spans.push(Span {
+                region: Region::Code,
+                range: self.code_start..start,
+            });
+        }
+        self.spans.push(Span {
+            region,
+            range: start..self.pos,
+        });
+        self.code_start = self.pos;
+    }
+
+    fn run(&mut self) {
+        while let Some(byte) = self.peek(0) {
+            let start = self.pos;
+            let after_ident = start > 0 && is_ident(self.source[start - 1]);
+
+            if self.starts_with(b"//") {
+                self.line_comment();
+                self.push(Region::Comment, start);
+            } else if self.starts_with(b"/*") {
+                self.block_comment();
+                self.push(Region::Comment, start);
+            } else if byte == b'"' {
+                self.pos += 1;
+                self.quoted(b'"');
+                self.push(Region::String, start);
+            } else if byte == b'\'' {
+                if self.char_literal() {
+                    self.push(Region::String, start);
+                }
+            } else if !after_ident && self.language == Language::Rust && self.rust_prefixed() {
+                self.push(Region::String, start);
+            } else {
+                self.pos += 1;
+            }
+        }
+
+        let end = self.source.len();
+        if self.code_start < end {
+            self.spans.push(Span {
+                region: Region::Code,
+                range: self.code_start..end,
+            });
+        }
+    }
+
+    fn line_comment(&mut self) {
+        while let Some(byte) = self.peek(0) {
+            // A trailing backslash continues a C comment onto the next line
+            if byte == b'\n' && !(self.language == Language::C && self.escaped_newline()) {
+                break;
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn escaped_newline(&self) -> bool {
+        let line = &self.source[..self.pos];
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        line.ends_with(b"\\")
+    }
+
+    fn block_comment(&mut self) {
+        self.pos += 2;
+        let mut depth = 1;
+        while depth > 0 && self.pos < self.source.len() {
+            if self.starts_with(b"*/") {
+                depth -= 1;
+                self.pos += 2;
+            } else if self.language == Language::Rust && self.starts_with(b"/*") {
+                depth += 1;
+                self.pos += 2;
+            } else {
+                self.pos += 1;
+            }
+        }
+    }
+
+    /// Consumes up to and including the closing `quote`, honouring escapes.
+    fn quoted(&mut self, quote: u8) {
+        while let Some(byte) = self.peek(0) {
+            self.pos += 1;
+            if byte == b'\\' {
+                self.pos = (self.pos + 1).min(self.source.len());
+            } else if byte == quote {
+                return;
+            }
+        }
+    }
+
+    /// Character literal, or in Rust possibly a lifetime or loop label.
+    fn char_literal(&mut self) -> bool {
+        if self.language == Language::C || self.peek(1) == Some(b'\\') {
+            self.pos += 1;
+            self.quoted(b'\'');
+            return true;
+        }
+
+        let rest = &self.source[self.pos + 1..];
+        let width = std::str::from_utf8(&rest[..rest.len().min(4)])
+            .or_else(|err| std::str::from_utf8(&rest[..err.valid_up_to()]))
+            .ok()
+            .and_then(|s| s.chars().next())
+            .map_or(1, char::len_utf8);
+        if rest.get(width) == Some(&b'\'') {
+            self.pos += width + 2;
+            true
+        } else {
+            self.pos += 1;
+            false
+        }
+    }
+
+    /// Byte, C and raw string literals: `b"..."`, `c"..."`, `r#"..."#`, ...
+    fn rust_prefixed(&mut self) -> bool {
+        let start = self.pos;
+        let mut pos = self.pos;
+        if matches!(self.source[pos], b'b' | b'c') {
+            pos += 1;
+        }
+        let raw = self.source.get(pos) == Some(&b'r');
+        if raw {
+            pos += 1;
+        }
+        if pos == start {
+            return false;
+        }
+
+        let hashes = self.source[pos..]
+            .iter()
+            .take_while(|&&b| b == b'#')
+            .count();
+        if self.source.get(pos + hashes) != Some(&b'"') || (!raw && hashes > 0) {
+            // `b'x'` byte literals are the only other prefixed form
+            if !raw && pos == start + 1 && self.source.get(pos) == Some(&b'\'') {
+                self.pos = pos;
+                return self.char_literal();
+            }
+            return false;
+        }
+
+        self.pos = pos + hashes + 1;
+        if !raw {
+            self.quoted(b'"');
+            return true;
+        }
+
+        let mut terminator = vec![b'"'];
+        terminator.extend(std::iter::repeat_n(b'#', hashes));
+        while self.pos < self.source.len() && !self.starts_with(&terminator) {
+            self.pos += 1;
+        }
+        self.pos = (self.pos + terminator.len()).min(self.source.len());
+        true
+    }
+}
+
+fn is_ident(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The text of every span of `source` in `region`.
+    fn regions(source: &str, language: Language, region: Region) -> Vec<&str> {
+        lex(source.as_bytes(), language)
+            .into_iter()
+            .filter(|span| span.region == region)
+            .map(|span| &source[span.range])
+            .collect()
+    }
+
+    fn strings(source: &str, language: Language) -> Vec<&str> {
+        regions(source, language, Region::String)
+    }
+
+    fn comments(source: &str, language: Language) -> Vec<&str> {
+        regions(source, language, Region::Comment)
+    }
+
+    #[test]
+    fn spans_cover_every_byte() {
+        let source = b"fn main() { let s = \"a\"; } // end\n/* unterminated";
+        let spans = lex(source, Language::Rust);
+        assert_eq!(spans.first().unwrap().range.start, 0);
+        assert_eq!(spans.last().unwrap().range.end, source.len());
+        for pair in spans.windows(2) {
+            assert_eq!(pair[0].range.end, pair[1].range.start);
+        }
+    }
+
+    #[test]
+    fn rust_raw_strings() {
+        assert_eq!(
+            strings(r###"let s = r#"a "quoted" word"#;"###, Language::Rust),
+            [r###"r#"a "quoted" word"#"###]
+        );
+        assert_eq!(
+            strings(r####"r##"ends with "# only"## + r"\"####, Language::Rust),
+            [r####"r##"ends with "# only"##"####, r#"r"\"#]
+        );
+        assert_eq!(
+            strings(r###"br#"bytes"# c"c str" b"\"x""###, Language::Rust),
+            [r###"br#"bytes"#"###, r#"c"c str""#, r#"b"\"x""#]
+        );
+        // Identifiers ending in r, b or c aren't prefixes
+        assert_eq!(
+            strings(r#"for"x" abc"y""#, Language::Rust),
+            [r#""x""#, r#""y""#]
+        );
+    }
+
+    #[test]
+    fn rust_nested_block_comments() {
+        assert_eq!(
+            comments("a /* outer /* inner */ still */ b", Language::Rust),
+            ["/* outer /* inner */ still */"]
+        );
+        // C comments don't nest
+        assert_eq!(
+            comments("a /* outer /* inner */ b */", Language::C),
+            ["/* outer /* inner */"]
+        );
+    }
+
+    #[test]
+    fn rust_lifetimes_and_chars() {
+        assert_eq!(
+            strings("fn f<'a>(x: &'a str) {}", Language::Rust),
+            Vec::<&str>::new()
+        );
+        assert_eq!(strings("let c = 'a';", Language::Rust), ["'a'"]);
+        assert_eq!(
+            strings(r"['\'', '\\', 'é', b'x', '\u{1F600}']", Language::Rust),
+            [r"'\''", r"'\\'", "'é'", "b'x'", r"'\u{1F600}'"]
+        );
+        // A label, then a char right after a lifetime
+        assert_eq!(
+            strings("'outer: loop { f::<'a>('b') }", Language::Rust),
+            ["'b'"]
+        );
+    }
+
+    #[test]
+    fn escaped_quotes() {
+        assert_eq!(
+            strings(
+                r#"s = "say \"hi\" // not a comment"; // comment"#,
+                Language::Rust
+            ),
+            [r#""say \"hi\" // not a comment""#]
+        );
+        assert_eq!(
+            comments(r#"s = "a\\"; // comment"#, Language::C),
+            ["// comment"]
+        );
+        assert_eq!(
+            strings(r#"c = '"'; s = "'";"#, Language::C),
+            [r#"'"'"#, r#""'""#]
+        );
+    }
+
+    #[test]
+    fn c_line_continuations() {
+        assert_eq!(
+            comments("// one \\\n two\nint x;", Language::C),
+            ["// one \\\n two"]
+        );
+        assert_eq!(
+            comments("// one \\\r\n two\r\nint x;", Language::C),
+            ["// one \\\r\n two\r"]
+        );
+        // Rust line comments end at the newline regardless
+        assert_eq!(
+            comments("// one \\\nlet two;", Language::Rust),
+            ["// one \\"]
+        );
+    }
+
+    #[test]
+    fn region_map_marks_every_byte() {
+        let source = b"x /*c*/ \"s\"";
+        let map = region_map(source, Language::C);
+        assert_eq!(map.len(), source.len());
+        assert_eq!(map[0], Region::Code);
+        assert!(map[2..7].iter().all(|&region| region == Region::Comment));
+        assert_eq!(map[7], Region::Code);
+        assert!(map[8..].iter().all(|&region| region == Region::String));
+    }
+}